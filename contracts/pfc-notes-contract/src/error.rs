@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Timestamp};
 use cw_ownable::OwnershipError;
 
 #[derive(Debug, thiserror::Error)]
@@ -10,4 +10,112 @@ pub enum ContractError {
     Ownership(#[from] OwnershipError),
     #[error("Address does not exist: %0")]
     AddressDoesNotExist(String),
+    #[error("Only one of expires_at and ttl_seconds can be set")]
+    ConflictingExpiry,
+    #[error("ttl_seconds of {0} is too large")]
+    TtlTooLarge(u64),
+    #[error("trash_retention of {0} is too large")]
+    RetentionTooLarge(u64),
+    #[error("Note expiry must be in the future: {0}")]
+    ExpiryInPast(Timestamp),
+    #[error("History depth {0} is larger than the maximum of {1}")]
+    HistoryDepthTooLarge(u32, u32),
+    #[error("max_note_size must be larger than zero")]
+    NoteSizeZero,
+    #[error("Revision {1} of note {0} is not available")]
+    RevisionNotFound(String, u64),
+    #[error("Note {note} has changed: expected revision {expected}, found {actual}")]
+    RevisionMismatch {
+        note: String,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("Note is not valid JSON: {0}")]
+    InvalidJson(String),
+    #[error("Note is not valid base64: {0}")]
+    InvalidBase64(String),
+    #[error("Note is not a valid IPFS CID: {0}")]
+    InvalidIpfsCid(String),
+    #[error("Note is not valid Markdown: {0}")]
+    InvalidMarkdown(String),
+    #[error("Note is not a valid URL: {0}")]
+    InvalidUrl(String),
+    #[error("Note is not a valid Arweave transaction id: {0}")]
+    InvalidArweaveId(String),
+    #[error("Invalid MIME type: {0}")]
+    InvalidMimeType(String),
+    #[error("Note does not decompress: {0}")]
+    InvalidCompression(String),
+    #[error("Invalid tag: {0:?}")]
+    InvalidTag(String),
+    #[error("A note can have at most {1} tags, got {0}")]
+    TooManyTags(usize, usize),
+    #[error("A note can have at most {1} links, got {0}")]
+    TooManyLinks(usize, usize),
+    #[error("Note {0} is not a {1} note")]
+    WrongNoteKind(String, String),
+    #[error("Counter {0} would overflow")]
+    CounterOverflow(String),
+    #[error("Counter {0} would go below 0")]
+    CounterUnderflow(String),
+    #[error("Alias loop at {0}")]
+    AliasLoop(String),
+    #[error("Alias {0} is more than {1} aliases from a note")]
+    AliasTooDeep(String, usize),
+    #[error("Can not move {0} onto itself")]
+    MoveToSelf(String),
+    #[error("Note {0} has no draft")]
+    NoDraft(String),
+    #[error("Note {0} is not in the trash")]
+    NotInTrash(String),
+    #[error("Note {0} is in the trash, restore or purge it first")]
+    Trashed(String),
+    #[error("Name {0} is already in use")]
+    NameTaken(String),
+    #[error("Map {0} is full at {1} fields")]
+    MapFull(String, u32),
+    #[error("Invalid field name: {0:?}")]
+    InvalidFieldName(String),
+    #[error("Map {0} has no field {1:?}")]
+    FieldNotFound(String, String),
+    #[error("List {0} is full at {1} items")]
+    ListFull(String, u32),
+    #[error("A list holds between 1 and {1} items, got a capacity of {0}")]
+    InvalidCapacity(u32, u32),
+    #[error("Invalid patch: {0}")]
+    InvalidPatch(String),
+    #[error("Invalid topic info: {0}")]
+    InvalidTopicInfo(String),
+    #[error("No note type given and {0} has no default note type")]
+    NoteTypeRequired(String),
+    #[error("No upload in progress for note {0}")]
+    NoUpload(String),
+    #[error("Note {0} has an upload in progress")]
+    UploadPending(String),
+    #[error("An upload has between 1 and {1} chunks, got {0}")]
+    InvalidChunkCount(u32, u32),
+    #[error("Chunk {index} is out of range, the upload has {total_chunks} chunks")]
+    ChunkOutOfRange {
+        index: u32,
+        total_chunks: u32,
+    },
+    #[error("Upload is missing {0} chunks")]
+    MissingChunks(u32),
+    #[error("Upload chunks add up to {received} bytes, {total_size} were expected")]
+    UploadSizeMismatch {
+        total_size: u32,
+        received: u64,
+    },
+    #[error("Invalid sha256 hash: {0}")]
+    InvalidHash(String),
+    #[error("Uploaded note hash {actual} does not match {expected}")]
+    HashMismatch {
+        expected: String,
+        actual: String,
+    },
+    #[error("Note is too large: {size} bytes, the limit is {limit} bytes")]
+    NoteTooLarge {
+        limit: u32,
+        size: usize,
+    },
 }
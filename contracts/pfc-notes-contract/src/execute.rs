@@ -1,25 +1,341 @@
-use cosmwasm_std::{Addr, DepsMut, Response, Storage};
-use pfc_notes::{NoteEntry, NoteKey, NoteType, SubTopicKey};
+use cosmwasm_std::{Addr, DepsMut, Env, Order, Response, StdResult, Storage, Timestamp};
+use cw_storage_plus::{Bound, KeyDeserialize};
+use pfc_notes::{
+    codec, Compression, ListItem, NoteAlias, NoteField, NoteKey, NoteKind, NotePatch, NoteStamp,
+    NoteType, PendingUpload, SubTopicKey, TopicInfo,
+};
+use sha2::{Digest, Sha256};
 
 use crate::{
     error::ContractError,
-    state::{notes, topic_subtopics, TOPICSMAP, WRITER},
+    json::Json,
+    patch::{json_patch, merge_patch},
+    state::{
+        aliases, notes, topic_subtopics, trash, NoteBody, NoteMeta, NotePk, TrashEntry, CONFIG,
+        DRAFTS, LISTS, LIST_ITEMS, MAP_FIELDS, MAX_ALIAS_DEPTH, MAX_HISTORY_DEPTH, MAX_LIST_LEN,
+        MAX_LIST_SIZE, MAX_MAP_FIELDS, MAX_MAP_SIZE, MAX_UPLOAD_CHUNKS, NOTE_BODIES, NOTE_HISTORY,
+        SUBTOPIC_INFO, TOPICSMAP, TOPIC_INFO, TOPIC_NOTE_SIZE, UPLOADS, UPLOAD_CHUNKS, WRITER,
+    },
+    validation::{
+        validate_field_name, validate_links, validate_note, validate_payload, validate_tags,
+        validate_topic_info,
+    },
 };
 
+const DEFAULT_BATCH_LIMIT: u32 = 10;
+const MAX_BATCH_LIMIT: u32 = 50;
+/// a move, copy, removal or purge batch stops once it has written this many entries, counting
+/// each note's history, items, fields and the aliases and links that point at it. the first note
+/// of a batch always goes, however many entries it has
+const MAX_BATCH_WRITES: usize = 1000;
+
+/// the optional parts of an `AddNote`
+#[derive(Default)]
+pub struct NoteOptions {
+    pub expires_at: Option<Timestamp>,
+    pub ttl_seconds: Option<u64>,
+    pub expected_revision: Option<u64>,
+    pub tags: Option<Vec<String>>,
+    pub compression: Option<Compression>,
+    pub links: Option<Vec<NoteKey>>,
+    /// save as the note's draft instead of publishing
+    pub draft: bool,
+}
+
+impl NoteOptions {
+    /// the expiry to write. one already in the past is refused, unless the note has it already
+    fn expiry(
+        &self,
+        env: &Env,
+        current: Option<Timestamp>,
+    ) -> Result<Option<Timestamp>, ContractError> {
+        let expires_at = match (self.expires_at, self.ttl_seconds) {
+            (Some(_), Some(_)) => return Err(ContractError::ConflictingExpiry),
+            (Some(expires_at), None) => expires_at,
+            (None, Some(ttl)) => {
+                checked_plus_seconds(env.block.time, ttl).ok_or(ContractError::TtlTooLarge(ttl))?
+            },
+            (None, None) => return Ok(None),
+        };
+        if expires_at <= env.block.time && Some(expires_at) != current {
+            return Err(ContractError::ExpiryInPast(expires_at));
+        }
+        Ok(Some(expires_at))
+    }
+}
+
+/// `time` plus `seconds`, or `None` past the largest timestamp
+fn checked_plus_seconds(time: Timestamp, seconds: u64) -> Option<Timestamp> {
+    seconds
+        .checked_mul(1_000_000_000)
+        .and_then(|nanos| time.nanos().checked_add(nanos))
+        .map(Timestamp::from_nanos)
+}
+
 pub fn assert_is_writer(store: &dyn Storage, addr: &Addr) -> Result<(), ContractError> {
     WRITER.load(store, addr.clone())?;
     Ok(())
 }
+
+pub fn assert_history_depth(history_depth: u32) -> Result<(), ContractError> {
+    if history_depth > MAX_HISTORY_DEPTH {
+        return Err(ContractError::HistoryDepthTooLarge(history_depth, MAX_HISTORY_DEPTH));
+    }
+    Ok(())
+}
+
+pub fn assert_max_note_size(max_note_size: u32) -> Result<(), ContractError> {
+    if max_note_size == 0 {
+        return Err(ContractError::NoteSizeZero);
+    }
+    Ok(())
+}
+
+pub fn max_note_size(store: &dyn Storage, topic: &str) -> StdResult<u32> {
+    match TOPIC_NOTE_SIZE.may_load(store, topic)? {
+        Some(max_note_size) => Ok(max_note_size),
+        None => Ok(CONFIG.load(store)?.max_note_size),
+    }
+}
+
+/// returns the limit checked against
+fn assert_note_size(store: &dyn Storage, topic: &str, note: &str) -> Result<u32, ContractError> {
+    let limit = max_note_size(store, topic)?;
+    if note.len() > limit as usize {
+        return Err(ContractError::NoteTooLarge {
+            limit,
+            size: note.len(),
+        });
+    }
+    Ok(limit)
+}
+
+/// the note type given, or else the subtopic's default, or else the topic's
+pub fn resolve_note_type(
+    store: &dyn Storage,
+    topic: &str,
+    sub_topic: &str,
+    note_type: Option<NoteType>,
+) -> Result<NoteType, ContractError> {
+    if let Some(note_type) = note_type {
+        return Ok(note_type);
+    }
+    let sub_topic_default =
+        SUBTOPIC_INFO.may_load(store, (topic, sub_topic))?.and_then(|info| info.default_note_type);
+    if let Some(note_type) = sub_topic_default {
+        return Ok(note_type);
+    }
+    TOPIC_INFO.may_load(store, topic)?.and_then(|info| info.default_note_type).ok_or_else(|| {
+        let subtopic_key = SubTopicKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+        };
+        ContractError::NoteTypeRequired(subtopic_key.to_string())
+    })
+}
+
+/// lowercase hex encoded sha256 of `data`
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// normalises a hex encoded sha256 to lowercase
+fn parse_sha256(hash: &str) -> Result<String, ContractError> {
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ContractError::InvalidHash(hash.to_string()));
+    }
+    Ok(hash.to_ascii_lowercase())
+}
+
+/// optimistic concurrency check. a missing note is at revision 0
+fn assert_revision(
+    note_key: &NoteKey,
+    expected: Option<u64>,
+    existing: Option<&NoteMeta>,
+) -> Result<(), ContractError> {
+    let actual = existing.map(|e| e.revision).unwrap_or_default();
+    match expected {
+        Some(expected) if expected != actual => Err(ContractError::RevisionMismatch {
+            note: note_key.to_string(),
+            expected,
+            actual,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// stores `note` under `hash`, or takes another reference to the copy already stored
+pub fn add_body(store: &mut dyn Storage, hash: &str, note: &str) -> StdResult<()> {
+    let body = match NOTE_BODIES.may_load(store, hash)? {
+        Some(body) => NoteBody {
+            refs: body.refs + 1,
+            ..body
+        },
+        None => NoteBody {
+            note: note.to_string(),
+            refs: 1,
+        },
+    };
+    NOTE_BODIES.save(store, hash, &body)
+}
+
+/// takes another reference to a body that is already stored
+fn retain_body(store: &mut dyn Storage, hash: &str) -> StdResult<()> {
+    let mut body = NOTE_BODIES.load(store, hash)?;
+    body.refs += 1;
+    NOTE_BODIES.save(store, hash, &body)
+}
+
+/// drops a reference to a body, removing the body once nothing holds it
+fn release_body(store: &mut dyn Storage, hash: &str) -> StdResult<()> {
+    let mut body = NOTE_BODIES.load(store, hash)?;
+    if body.refs <= 1 {
+        NOTE_BODIES.remove(store, hash);
+        return Ok(());
+    }
+    body.refs -= 1;
+    NOTE_BODIES.save(store, hash, &body)
+}
+
+fn assert_kind(note_key: &NoteKey, meta: &NoteMeta, kind: &NoteKind) -> Result<(), ContractError> {
+    if std::mem::discriminant(&meta.kind) != std::mem::discriminant(kind) {
+        return Err(ContractError::WrongNoteKind(note_key.to_string(), kind.to_string()));
+    }
+    Ok(())
+}
+
+/// saves `note_meta`, moving the note it replaces (if any) into the history and trimming the
+/// history down to the configured depth. the caller holds a body reference for `note_meta`
+fn save_note(
+    store: &mut dyn Storage,
+    pk: NotePk,
+    existing: Option<NoteMeta>,
+    note_meta: &NoteMeta,
+) -> Result<(), ContractError> {
+    if let Some(existing) = existing {
+        let history_depth = CONFIG.load(store)?.history_depth as u64;
+        if history_depth > 0 {
+            NOTE_HISTORY.save(store, (pk, existing.revision), &existing)?;
+        } else {
+            release_body(store, &existing.hash)?;
+        }
+        let oldest_kept = note_meta.revision.saturating_sub(history_depth);
+        let stale = NOTE_HISTORY
+            .prefix(pk)
+            .range(store, None, Some(Bound::exclusive(oldest_kept)), Order::Ascending)
+            .collect::<StdResult<Vec<(u64, NoteMeta)>>>()?;
+        for (revision, meta) in stale {
+            NOTE_HISTORY.remove(store, (pk, revision));
+            release_body(store, &meta.hash)?;
+        }
+    }
+    notes().save(store, pk, note_meta)?;
+    Ok(())
+}
+
+/// removes a note along with its history, draft and any upload to it, releasing their bodies.
+/// returns how many entries were removed
+fn remove_note(store: &mut dyn Storage, pk: NotePk) -> StdResult<usize> {
+    let meta = notes().load(store, pk)?;
+    notes().remove(store, pk)?;
+    let uploads = clear_upload(store, pk)?;
+    let drafts = clear_draft(store, pk)?;
+    Ok(1 + uploads + drafts + remove_note_data(store, pk, &meta.hash)?)
+}
+
+/// drops the draft kept under a note's key, if there is one, releasing its body. returns how
+/// many entries were removed
+fn clear_draft(store: &mut dyn Storage, pk: NotePk) -> StdResult<usize> {
+    match DRAFTS.may_load(store, pk)? {
+        Some(draft) => {
+            DRAFTS.remove(store, pk);
+            release_body(store, &draft.hash)?;
+            Ok(1)
+        },
+        None => Ok(0),
+    }
+}
+
+/// removes what is kept under a note's key besides the note itself, and releases its body.
+/// returns how many entries were removed
+fn remove_note_data(store: &mut dyn Storage, pk: NotePk, hash: &str) -> StdResult<usize> {
+    let history = NOTE_HISTORY
+        .prefix(pk)
+        .range(store, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(u64, NoteMeta)>>>()?;
+    let mut removed = history.len();
+    for (revision, meta) in history {
+        NOTE_HISTORY.remove(store, (pk, revision));
+        release_body(store, &meta.hash)?;
+    }
+    if let Some(list) = LISTS.may_load(store, pk)? {
+        for index in list.first..list.next {
+            LIST_ITEMS.remove(store, (pk, index));
+        }
+        LISTS.remove(store, pk);
+        removed += 1 + (list.next - list.first) as usize;
+    }
+    let fields = MAP_FIELDS
+        .prefix(pk)
+        .keys(store, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    removed += fields.len();
+    for field in fields {
+        MAP_FIELDS.remove(store, (pk, &field));
+    }
+    release_body(store, hash)?;
+    Ok(removed)
+}
+
+/// checks a new note can take the name. a note in the trash keeps it until it is restored or
+/// purged
+fn claim_name(store: &dyn Storage, pk: NotePk) -> Result<(), ContractError> {
+    let (topic, sub_topic, name) = pk;
+    if notes().has(store, pk) || aliases().has(store, pk) {
+        return Err(ContractError::NameTaken(NoteKey::from((topic, sub_topic, name)).to_string()));
+    }
+    if trash().has(store, pk) {
+        return Err(ContractError::Trashed(NoteKey::from((topic, sub_topic, name)).to_string()));
+    }
+    Ok(())
+}
+
+/// empties a note out of the trash for good, with everything it left under its key. returns how
+/// many entries were removed
+fn purge_trashed(store: &mut dyn Storage, pk: NotePk) -> StdResult<usize> {
+    let entry = trash().load(store, pk)?;
+    trash().remove(store, pk)?;
+    Ok(1 + remove_note_data(store, pk, &entry.meta.hash)?)
+}
+
+/// collects up to `budget` keys, and whether that was all of them
+fn take_batch<K>(
+    keys: impl Iterator<Item = StdResult<K>>,
+    budget: usize,
+) -> StdResult<(Vec<K>, bool)> {
+    // one extra, to know if any are left
+    let mut batch = keys.take(budget + 1).collect::<StdResult<Vec<K>>>()?;
+    let done = batch.len() <= budget;
+    batch.truncate(budget);
+    Ok((batch, done))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn exec_add_note(
     deps: DepsMut,
+    env: &Env,
     sender: &Addr,
     topic: &str,
     sub_topic: &str,
     name: &str,
     note_type: NoteType,
     note: &str,
+    options: NoteOptions,
 ) -> Result<Response, ContractError> {
     assert_is_writer(deps.storage, sender)?;
+    let limit = assert_note_size(deps.storage, topic, note)?;
+    let compression = options.compression.clone().unwrap_or_default();
+    validate_payload(&note_type, &compression, note, limit)?;
     let subtopic_key = SubTopicKey {
         topic: topic.to_string(),
         sub_topic: sub_topic.to_string(),
@@ -29,26 +345,891 @@ pub fn exec_add_note(
         sub_topic: sub_topic.to_string(),
         name: name.to_string(),
     };
-    let note_entry = NoteEntry {
+    let now = NoteStamp::from(&env.block);
+    let existing = notes().may_load(deps.storage, (topic, sub_topic, name))?;
+    assert_revision(&note_key, options.expected_revision, existing.as_ref())?;
+    let expires_at = options.expiry(env, existing.as_ref().and_then(|e| e.expires_at))?;
+    match &existing {
+        Some(existing) => assert_kind(&note_key, existing, &NoteKind::Plain)?,
+        // a draft does not take the name until it is published
+        None if options.draft => {
+            if aliases().has(deps.storage, (topic, sub_topic, name)) {
+                return Err(ContractError::NameTaken(note_key.to_string()));
+            }
+        },
+        None => {
+            claim_name(deps.storage, (topic, sub_topic, name))?;
+        },
+    }
+    let tags = match options.tags {
+        Some(tags) => validate_tags(tags)?,
+        None => existing.as_ref().map(|e| e.tags.clone()).unwrap_or_default(),
+    };
+    let links = match options.links {
+        Some(links) => validate_links(links)?,
+        None => existing.as_ref().map(|e| e.links.clone()).unwrap_or_default(),
+    };
+    let note_meta = NoteMeta {
         topic: topic.to_string(),
         sub_topic: sub_topic.to_string(),
         name: name.to_string(),
         writer: sender.clone(),
         note_type,
-        note: note.to_string(),
+        created_at: existing.as_ref().map(|e| e.created_at.clone()).unwrap_or_else(|| now.clone()),
+        updated_at: now,
+        revision: existing.as_ref().map(|e| e.revision + 1).unwrap_or(1),
+        expires_at,
+        tags,
+        compression,
+        hash: sha256_hex(note.as_bytes()),
+        links,
+        kind: NoteKind::Plain,
+    };
+    if options.draft {
+        add_body(deps.storage, &note_meta.hash, note)?;
+        if let Some(previous) = DRAFTS.may_load(deps.storage, (topic, sub_topic, name))? {
+            release_body(deps.storage, &previous.hash)?;
+        }
+        DRAFTS.save(deps.storage, (topic, sub_topic, name), &note_meta)?;
+        return Ok(Response::default()
+            .add_attribute("hash", note_meta.hash)
+            .add_attribute("draft", "true"));
+    }
+    TOPICSMAP.save(deps.storage, topic.to_string(), &topic.to_string())?;
+    topic_subtopics().save(deps.storage, (topic, sub_topic), &subtopic_key)?;
+    add_body(deps.storage, &note_meta.hash, note)?;
+    save_note(deps.storage, (topic, sub_topic, name), existing, &note_meta)?;
+    Ok(Response::default().add_attribute("hash", note_meta.hash))
+}
+
+pub fn exec_set_alias(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    target: NoteKey,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
     };
+    let pk = (topic, sub_topic, name);
+    if notes().has(deps.storage, pk) {
+        return Err(ContractError::NameTaken(note_key.to_string()));
+    }
+    // the alias has to reach a note, without looping back on itself
+    let mut resolved = target.clone();
+    let mut depth = 1;
+    loop {
+        if resolved == note_key {
+            return Err(ContractError::AliasLoop(note_key.to_string()));
+        }
+        let resolved_pk =
+            (resolved.topic.as_str(), resolved.sub_topic.as_str(), resolved.name.as_str());
+        let Some(alias) = aliases().may_load(deps.storage, resolved_pk)? else {
+            notes().load(deps.storage, resolved_pk)?;
+            break;
+        };
+        depth += 1;
+        if depth > MAX_ALIAS_DEPTH {
+            return Err(ContractError::AliasTooDeep(note_key.to_string(), MAX_ALIAS_DEPTH));
+        }
+        resolved = alias.target;
+    }
+    // aliases already pointing at this name get the same chain behind them
+    let mut level = vec![(topic.to_string(), sub_topic.to_string(), name.to_string())];
+    loop {
+        let mut above = vec![];
+        for key in level {
+            let keys = aliases()
+                .idx
+                .target
+                .prefix(key)
+                .keys(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<(String, String, String)>>>()?;
+            above.extend(keys);
+        }
+        if above.is_empty() {
+            break;
+        }
+        depth += 1;
+        if depth > MAX_ALIAS_DEPTH {
+            let (topic, sub_topic, name) = above.swap_remove(0);
+            let key = NoteKey {
+                topic,
+                sub_topic,
+                name,
+            };
+            return Err(ContractError::AliasTooDeep(key.to_string(), MAX_ALIAS_DEPTH));
+        }
+        level = above;
+    }
+    aliases().save(
+        deps.storage,
+        pk,
+        &NoteAlias {
+            target,
+            writer: sender.clone(),
+            updated_at: NoteStamp::from(&env.block),
+        },
+    )?;
+
     TOPICSMAP.save(deps.storage, topic.to_string(), &topic.to_string())?;
-    topic_subtopics().save(deps.storage, subtopic_key.to_string(), &subtopic_key)?;
-    notes().save(deps.storage, note_key.to_string(), &note_entry)?;
+    topic_subtopics().save(
+        deps.storage,
+        (topic, sub_topic),
+        &SubTopicKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+        },
+    )?;
+    Ok(Response::default().add_attribute("target", resolved.to_string()))
+}
+
+pub fn exec_remove_alias(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    aliases().load(deps.storage, (topic, sub_topic, name))?;
+    aliases().remove(deps.storage, (topic, sub_topic, name))?;
     Ok(Response::default())
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn exec_publish_note(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    expected_revision: Option<u64>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let pk = (topic, sub_topic, name);
+    let draft = DRAFTS
+        .may_load(deps.storage, pk)?
+        .ok_or_else(|| ContractError::NoDraft(note_key.to_string()))?;
+    let existing = notes().may_load(deps.storage, pk)?;
+    assert_revision(&note_key, expected_revision, existing.as_ref())?;
+    match &existing {
+        Some(existing) => assert_kind(&note_key, existing, &NoteKind::Plain)?,
+        None => {
+            claim_name(deps.storage, pk)?;
+        },
+    }
+    if let Some(expires_at) = draft.expires_at {
+        if expires_at <= env.block.time {
+            return Err(ContractError::ExpiryInPast(expires_at));
+        }
+    }
+    let now = NoteStamp::from(&env.block);
+    let note_meta = NoteMeta {
+        created_at: existing.as_ref().map(|e| e.created_at.clone()).unwrap_or_else(|| now.clone()),
+        updated_at: now,
+        revision: existing.as_ref().map(|e| e.revision + 1).unwrap_or(1),
+        ..draft
+    };
+    DRAFTS.remove(deps.storage, pk);
+    TOPICSMAP.save(deps.storage, topic.to_string(), &topic.to_string())?;
+    topic_subtopics().save(
+        deps.storage,
+        (topic, sub_topic),
+        &SubTopicKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+        },
+    )?;
+    // the draft's reference to its body passes to the note
+    save_note(deps.storage, pk, existing, &note_meta)?;
+    Ok(Response::default().add_attribute("revision", note_meta.revision.to_string()))
+}
+
+pub fn exec_discard_draft(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let pk = (topic, sub_topic, name);
+    let draft = DRAFTS.may_load(deps.storage, pk)?.ok_or_else(|| {
+        ContractError::NoDraft(
+            NoteKey {
+                topic: topic.to_string(),
+                sub_topic: sub_topic.to_string(),
+                name: name.to_string(),
+            }
+            .to_string(),
+        )
+    })?;
+    DRAFTS.remove(deps.storage, pk);
+    release_body(deps.storage, &draft.hash)?;
+    Ok(Response::default())
+}
+
+/// drops any upload in progress for the note, with its chunks. returns how many entries were
+/// removed
+fn clear_upload(store: &mut dyn Storage, pk: NotePk) -> StdResult<usize> {
+    if !UPLOADS.has(store, pk) {
+        return Ok(0);
+    }
+    let indexes = UPLOAD_CHUNKS
+        .prefix(pk)
+        .keys(store, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<u32>>>()?;
+    let removed = 1 + indexes.len();
+    for index in indexes {
+        UPLOAD_CHUNKS.remove(store, (pk, index));
+    }
+    UPLOADS.remove(store, pk);
+    Ok(removed)
+}
+
+/// fails if an upload to the note is in progress, as it would land under the old key
+fn assert_no_upload(store: &dyn Storage, pk: NotePk) -> Result<(), ContractError> {
+    if UPLOADS.has(store, pk) {
+        return Err(ContractError::UploadPending(NoteKey::from(pk).to_string()));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exec_begin_upload(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    note_type: Option<NoteType>,
+    total_chunks: u32,
+    total_size: u32,
+    sha256: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_type = resolve_note_type(deps.storage, topic, sub_topic, note_type)?;
+    if total_chunks == 0 || total_chunks > MAX_UPLOAD_CHUNKS {
+        return Err(ContractError::InvalidChunkCount(total_chunks, MAX_UPLOAD_CHUNKS));
+    }
+    // the assembled note is written as any other, so it is held to the same limit
+    let limit = max_note_size(deps.storage, topic)?;
+    if total_size > limit {
+        return Err(ContractError::NoteTooLarge {
+            limit,
+            size: total_size as usize,
+        });
+    }
+    let upload = PendingUpload {
+        uploader: sender.clone(),
+        note_type,
+        total_chunks,
+        total_size,
+        received_size: 0,
+        sha256: parse_sha256(sha256)?,
+        started_at: NoteStamp::from(&env.block),
+    };
+    clear_upload(deps.storage, (topic, sub_topic, name))?;
+    UPLOADS.save(deps.storage, (topic, sub_topic, name), &upload)?;
+    Ok(Response::default())
+}
+
+pub fn exec_upload_chunk(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    index: u32,
+    data: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let upload = UPLOADS
+        .may_load(deps.storage, (topic, sub_topic, name))?
+        .ok_or_else(|| ContractError::NoUpload(note_key.to_string()))?;
+    if index >= upload.total_chunks {
+        return Err(ContractError::ChunkOutOfRange {
+            index,
+            total_chunks: upload.total_chunks,
+        });
+    }
+    // a chunk sent again replaces the one before it
+    let replaced = UPLOAD_CHUNKS
+        .may_load(deps.storage, ((topic, sub_topic, name), index))?
+        .map_or(0, |chunk| chunk.len() as u64);
+    let received = u64::from(upload.received_size) - replaced + data.len() as u64;
+    if received > u64::from(upload.total_size) {
+        return Err(ContractError::UploadSizeMismatch {
+            total_size: upload.total_size,
+            received,
+        });
+    }
+    UPLOAD_CHUNKS.save(deps.storage, ((topic, sub_topic, name), index), &data.to_string())?;
+    UPLOADS.save(
+        deps.storage,
+        (topic, sub_topic, name),
+        &PendingUpload {
+            received_size: received as u32,
+            ..upload
+        },
+    )?;
+    Ok(Response::default())
+}
+
+pub fn exec_abort_upload(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let removed = clear_upload(deps.storage, (topic, sub_topic, name))?;
+    if removed == 0 {
+        return Err(ContractError::NoUpload(NoteKey::from((topic, sub_topic, name)).to_string()));
+    }
+    Ok(Response::default().add_attribute("removed_chunks", (removed - 1).to_string()))
+}
+
+pub fn exec_finalize_upload(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let upload = UPLOADS
+        .may_load(deps.storage, (topic, sub_topic, name))?
+        .ok_or_else(|| ContractError::NoUpload(note_key.to_string()))?;
+    let chunks = UPLOAD_CHUNKS
+        .prefix((topic, sub_topic, name))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, v)| v))
+        .collect::<StdResult<Vec<String>>>()?;
+    let received = chunks.len() as u32;
+    if received < upload.total_chunks {
+        return Err(ContractError::MissingChunks(upload.total_chunks - received));
+    }
+    if upload.received_size != upload.total_size {
+        return Err(ContractError::UploadSizeMismatch {
+            total_size: upload.total_size,
+            received: u64::from(upload.received_size),
+        });
+    }
+    let note = chunks.concat();
+    let actual = sha256_hex(note.as_bytes());
+    if actual != upload.sha256 {
+        return Err(ContractError::HashMismatch {
+            expected: upload.sha256,
+            actual,
+        });
+    }
+    clear_upload(deps.storage, (topic, sub_topic, name))?;
+    exec_add_note(
+        deps,
+        env,
+        sender,
+        topic,
+        sub_topic,
+        name,
+        upload.note_type,
+        &note,
+        NoteOptions::default(),
+    )
+}
+
+/// the meta of a note after a write that leaves its body alone, such as to a list or map
+fn touch_note(meta: NoteMeta, sender: &Addr, now: &NoteStamp) -> NoteMeta {
+    NoteMeta {
+        writer: sender.clone(),
+        updated_at: now.clone(),
+        revision: meta.revision + 1,
+        ..meta
+    }
+}
+
+/// saves a new note of a kind other than plain. its body is empty
+fn create_kind_note(
+    store: &mut dyn Storage,
+    sender: &Addr,
+    pk: NotePk,
+    note_type: NoteType,
+    kind: NoteKind,
+    now: &NoteStamp,
+) -> Result<NoteMeta, ContractError> {
+    let (topic, sub_topic, name) = pk;
+    claim_name(store, pk)?;
+    let meta = NoteMeta {
+        writer: sender.clone(),
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+        note_type,
+        created_at: now.clone(),
+        updated_at: now.clone(),
+        revision: 1,
+        expires_at: None,
+        tags: vec![],
+        compression: Compression::None,
+        hash: sha256_hex(b""),
+        links: vec![],
+        kind,
+    };
+    TOPICSMAP.save(store, topic.to_string(), &topic.to_string())?;
+    topic_subtopics().save(
+        store,
+        (topic, sub_topic),
+        &SubTopicKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+        },
+    )?;
+    add_body(store, &meta.hash, "")?;
+    notes().save(store, pk, &meta)?;
+    Ok(meta)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exec_append_to_note(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    note_type: Option<NoteType>,
+    item: &str,
+    capacity: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    assert_note_size(deps.storage, topic, item)?;
+    if let Some(capacity) = capacity {
+        if capacity == 0 || capacity > MAX_LIST_LEN {
+            return Err(ContractError::InvalidCapacity(capacity, MAX_LIST_LEN));
+        }
+    }
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let pk = (topic, sub_topic, name);
+    let now = NoteStamp::from(&env.block);
+
+    let (meta, created) = match notes().may_load(deps.storage, pk)? {
+        Some(meta) => {
+            assert_kind(
+                &note_key,
+                &meta,
+                &NoteKind::List {
+                    capacity: None,
+                },
+            )?;
+            (meta, false)
+        },
+        None => {
+            let note_type = resolve_note_type(deps.storage, topic, sub_topic, note_type)?;
+            let kind = NoteKind::List {
+                capacity,
+            };
+            (create_kind_note(deps.storage, sender, pk, note_type, kind, &now)?, true)
+        },
+    };
+    validate_note(&meta.note_type, item)?;
+
+    let current = match meta.kind {
+        NoteKind::List {
+            capacity,
+        } => capacity,
+        _ => None,
+    };
+    // appending to an existing list is a new revision of it
+    if !created {
+        let meta = NoteMeta {
+            kind: NoteKind::List {
+                capacity: capacity.or(current),
+            },
+            ..touch_note(meta, sender, &now)
+        };
+        notes().save(deps.storage, pk, &meta)?;
+    }
+    let mut list = LISTS.may_load(deps.storage, pk)?.unwrap_or_default();
+    let capped = capacity.or(current).is_some();
+    let keep = match capacity.or(current) {
+        Some(capacity) => capacity as u64,
+        None if list.next - list.first >= MAX_LIST_LEN as u64 => {
+            return Err(ContractError::ListFull(note_key.to_string(), MAX_LIST_LEN));
+        },
+        None => MAX_LIST_LEN as u64,
+    };
+    // a capped list makes room by dropping its oldest items, others have to stay within it
+    let size = if capped {
+        item.len() as u64
+    } else {
+        list.size + item.len() as u64
+    };
+    if size > MAX_LIST_SIZE as u64 {
+        return Err(ContractError::NoteTooLarge {
+            size: size as usize,
+            limit: MAX_LIST_SIZE,
+        });
+    }
+
+    let index = list.next;
+    LIST_ITEMS.save(
+        deps.storage,
+        (pk, index),
+        &ListItem {
+            index,
+            item: item.to_string(),
+            writer: sender.clone(),
+            appended_at: now,
+        },
+    )?;
+    list.next += 1;
+    list.size += item.len() as u64;
+    // the oldest items make way
+    while list.next - list.first > keep || list.size > MAX_LIST_SIZE as u64 {
+        let oldest = LIST_ITEMS.load(deps.storage, (pk, list.first))?;
+        LIST_ITEMS.remove(deps.storage, (pk, list.first));
+        list.size -= oldest.item.len() as u64;
+        list.first += 1;
+    }
+    LISTS.save(deps.storage, pk, &list)?;
+    Ok(Response::default().add_attribute("index", index.to_string()))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exec_set_note_field(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    note_type: Option<NoteType>,
+    field: &str,
+    value: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    assert_note_size(deps.storage, topic, value)?;
+    validate_field_name(field)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let pk = (topic, sub_topic, name);
+    let now = NoteStamp::from(&env.block);
+
+    let (meta, created) = match notes().may_load(deps.storage, pk)? {
+        Some(meta) => (meta, false),
+        None => {
+            let note_type = resolve_note_type(deps.storage, topic, sub_topic, note_type)?;
+            let kind = NoteKind::Map {
+                fields: 0,
+                size: 0,
+            };
+            (create_kind_note(deps.storage, sender, pk, note_type, kind, &now)?, true)
+        },
+    };
+    let NoteKind::Map {
+        fields,
+        size,
+    } = meta.kind
+    else {
+        return Err(ContractError::WrongNoteKind(note_key.to_string(), "map".to_string()));
+    };
+    validate_note(&meta.note_type, value)?;
+
+    let replaced = MAP_FIELDS.may_load(deps.storage, (pk, field))?;
+    let added = replaced.is_none();
+    if added && fields >= MAX_MAP_FIELDS {
+        return Err(ContractError::MapFull(note_key.to_string(), MAX_MAP_FIELDS));
+    }
+    // saturating, as maps written before sizes were kept start from 0
+    let size = (size + (field.len() + value.len()) as u64)
+        .saturating_sub(replaced.map_or(0, |old| (field.len() + old.value.len()) as u64));
+    if size > MAX_MAP_SIZE as u64 {
+        return Err(ContractError::NoteTooLarge {
+            size: size as usize,
+            limit: MAX_MAP_SIZE,
+        });
+    }
+    // setting a field of an existing map is a new revision of it
+    let meta = if created {
+        meta
+    } else {
+        touch_note(meta, sender, &now)
+    };
+    let meta = NoteMeta {
+        kind: NoteKind::Map {
+            fields: fields + added as u32,
+            size,
+        },
+        ..meta
+    };
+    notes().save(deps.storage, pk, &meta)?;
+    MAP_FIELDS.save(
+        deps.storage,
+        (pk, field),
+        &NoteField {
+            field: field.to_string(),
+            value: value.to_string(),
+            writer: sender.clone(),
+            updated_at: now,
+        },
+    )?;
+    Ok(Response::default().add_attribute("field", field))
+}
+
+pub fn exec_remove_note_field(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    field: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let pk = (topic, sub_topic, name);
+    let meta = notes().load(deps.storage, pk)?;
+    let NoteKind::Map {
+        fields,
+        size,
+    } = meta.kind
+    else {
+        return Err(ContractError::WrongNoteKind(note_key.to_string(), "map".to_string()));
+    };
+    let removed = MAP_FIELDS
+        .may_load(deps.storage, (pk, field))?
+        .ok_or_else(|| ContractError::FieldNotFound(note_key.to_string(), field.to_string()))?;
+    MAP_FIELDS.remove(deps.storage, (pk, field));
+    let meta = NoteMeta {
+        kind: NoteKind::Map {
+            fields: fields - 1,
+            size: size.saturating_sub((field.len() + removed.value.len()) as u64),
+        },
+        ..touch_note(meta, sender, &NoteStamp::from(&env.block))
+    };
+    notes().save(deps.storage, pk, &meta)?;
+    Ok(Response::default().add_attribute("field", field))
+}
+
+/// adds `by` to a counter note, creating it at 0 if needed
+pub fn exec_increment_note(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    by: u64,
+) -> Result<Response, ContractError> {
+    update_counter(
+        deps,
+        env,
+        sender,
+        (topic, sub_topic, name),
+        |value| value.checked_add(by),
+        ContractError::CounterOverflow,
+    )
+}
+
+/// takes `by` from a counter note, which can not go below 0
+pub fn exec_decrement_note(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    by: u64,
+) -> Result<Response, ContractError> {
+    update_counter(
+        deps,
+        env,
+        sender,
+        (topic, sub_topic, name),
+        |value| value.checked_sub(by),
+        ContractError::CounterUnderflow,
+    )
+}
+
+/// `out_of_range` is the error for an update that `update` can not make
+fn update_counter(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    pk: NotePk,
+    update: impl Fn(u64) -> Option<u64>,
+    out_of_range: fn(String) -> ContractError,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let (topic, sub_topic, name) = pk;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let now = NoteStamp::from(&env.block);
+    let (meta, created) = match notes().may_load(deps.storage, pk)? {
+        Some(meta) => (meta, false),
+        None => {
+            let kind = NoteKind::Counter {
+                value: 0,
+            };
+            (create_kind_note(deps.storage, sender, pk, NoteType::String, kind, &now)?, true)
+        },
+    };
+    let NoteKind::Counter {
+        value,
+    } = meta.kind
+    else {
+        return Err(ContractError::WrongNoteKind(note_key.to_string(), "counter".to_string()));
+    };
+    let value = update(value).ok_or_else(|| out_of_range(note_key.to_string()))?;
+    // updating an existing counter is a new revision of it, so `expected_revision` can guard it
+    let meta = if created {
+        meta
+    } else {
+        touch_note(meta, sender, &now)
+    };
+    let meta = NoteMeta {
+        kind: NoteKind::Counter {
+            value,
+        },
+        ..meta
+    };
+    notes().save(deps.storage, pk, &meta)?;
+    Ok(Response::default().add_attribute("value", value.to_string()))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exec_patch_note(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    patch: &NotePatch,
+    expected_revision: Option<u64>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let existing = notes().load(deps.storage, (topic, sub_topic, name))?;
+    if existing.note_type != NoteType::Json {
+        return Err(ContractError::InvalidPatch(format!(
+            "note is {}, not Json",
+            existing.note_type
+        )));
+    }
+    let payload = NOTE_BODIES.load(deps.storage, &existing.hash)?.note;
+    let body = codec::decode(&existing.compression, &payload)?;
+    let mut doc = Json::parse(&body).map_err(ContractError::InvalidJson)?;
+    match patch {
+        NotePatch::Merge(merge) => {
+            let merge = Json::parse(merge).map_err(ContractError::InvalidPatch)?;
+            merge_patch(&mut doc, &merge);
+        },
+        NotePatch::Json(operations) => {
+            let limit = max_note_size(deps.storage, topic)? as usize;
+            json_patch(&mut doc, operations, limit)?
+        },
+    }
+
+    // the contract only decompresses, so the patched body is stored as it is. the rest of the
+    // note is kept
+    exec_add_note(
+        deps,
+        env,
+        sender,
+        topic,
+        sub_topic,
+        name,
+        NoteType::Json,
+        &doc.to_string(),
+        NoteOptions {
+            expires_at: existing.expires_at,
+            expected_revision,
+            ..NoteOptions::default()
+        },
+    )
+}
+
+pub fn exec_revert_note(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    revision: u64,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let existing = notes().load(deps.storage, (topic, sub_topic, name))?;
+    let previous = NOTE_HISTORY
+        .may_load(deps.storage, ((topic, sub_topic, name), revision))?
+        .ok_or_else(|| ContractError::RevisionNotFound(note_key.to_string(), revision))?;
+
+    let note_meta = NoteMeta {
+        writer: sender.clone(),
+        note_type: previous.note_type,
+        compression: previous.compression,
+        hash: previous.hash,
+        updated_at: NoteStamp::from(&env.block),
+        revision: existing.revision + 1,
+        ..existing.clone()
+    };
+    retain_body(deps.storage, &note_meta.hash)?;
+    save_note(deps.storage, (topic, sub_topic, name), Some(existing), &note_meta)?;
+    Ok(Response::default().add_attribute("revision", note_meta.revision.to_string()))
+}
+
 pub fn exec_rm_note(
     deps: DepsMut,
     sender: &Addr,
     topic: &str,
     sub_topic: &str,
     name: &str,
+    expected_revision: Option<u64>,
 ) -> Result<Response, ContractError> {
     assert_is_writer(deps.storage, sender)?;
 
@@ -57,33 +1238,945 @@ pub fn exec_rm_note(
         sub_topic: sub_topic.to_string(),
         name: name.to_string(),
     };
-    notes().load(deps.storage, note_key.to_string())?;
-    // it exists.. now delete the stuff
-    notes().remove(deps.storage, note_key.to_string())?;
+    let existing = notes().load(deps.storage, (topic, sub_topic, name))?;
+    assert_revision(&note_key, expected_revision, Some(&existing))?;
+    // it exists.. now delete the stuff, its draft included
+    let removed_drafts = DRAFTS.has(deps.storage, (topic, sub_topic, name)) as usize;
+    remove_note(deps.storage, (topic, sub_topic, name))?;
 
-    Ok(Response::default())
+    Ok(Response::default().add_attribute("removed_drafts", removed_drafts.to_string()))
 }
-pub fn exec_rm_sub_topic(
+/// moves a note to the trash. its body, history and items stay under its key until it is
+/// restored or purged
+#[allow(clippy::too_many_arguments)]
+pub fn exec_trash_note(
     deps: DepsMut,
+    env: &Env,
     sender: &Addr,
     topic: &str,
     sub_topic: &str,
+    name: &str,
+    expected_revision: Option<u64>,
 ) -> Result<Response, ContractError> {
     assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let pk = (topic, sub_topic, name);
+    let existing = notes().load(deps.storage, pk)?;
+    assert_revision(&note_key, expected_revision, Some(&existing))?;
+    let retention = CONFIG.load(deps.storage)?.trash_retention;
+    let purge_after = checked_plus_seconds(env.block.time, retention)
+        .ok_or(ContractError::RetentionTooLarge(retention))?;
+    notes().remove(deps.storage, pk)?;
+    clear_upload(deps.storage, pk)?;
+    trash().save(
+        deps.storage,
+        pk,
+        &TrashEntry {
+            meta: existing,
+            removed_by: sender.clone(),
+            removed_at: NoteStamp::from(&env.block),
+            purge_after,
+        },
+    )?;
+    Ok(Response::default().add_attribute("purge_after", purge_after.to_string()))
+}
 
-    let subtopic_key = SubTopicKey {
+pub fn exec_restore_note(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let note_key = NoteKey {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+    };
+    let pk = (topic, sub_topic, name);
+    let entry = trash()
+        .may_load(deps.storage, pk)?
+        .ok_or_else(|| ContractError::NotInTrash(note_key.to_string()))?;
+    if notes().has(deps.storage, pk) || aliases().has(deps.storage, pk) {
+        return Err(ContractError::NameTaken(note_key.to_string()));
+    }
+    trash().remove(deps.storage, pk)?;
+    notes().save(deps.storage, pk, &entry.meta)?;
+    TOPICSMAP.save(deps.storage, topic.to_string(), &topic.to_string())?;
+    topic_subtopics().save(
+        deps.storage,
+        (topic, sub_topic),
+        &SubTopicKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+        },
+    )?;
+    Ok(Response::default())
+}
+
+pub fn exec_purge_trash(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let limit_amt = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_BATCH_LIMIT) as usize;
+
+    // everything due at or before the current block time
+    let due = trash()
+        .idx
+        .purge
+        .keys(
+            deps.storage,
+            None,
+            Some(Bound::exclusive((env.block.time.nanos() + 1, ("", "", "")))),
+            Order::Ascending,
+        )
+        .take(limit_amt)
+        .collect::<StdResult<Vec<(String, String, String)>>>()?;
+    let mut writes = 0;
+    let purged = run_batch(&due, &mut writes, |(topic, sub_topic, name)| {
+        let pk = (topic.as_str(), sub_topic.as_str(), name.as_str());
+        Ok(purge_trashed(deps.storage, pk)? + clear_draft(deps.storage, pk)?)
+    })?;
+
+    Ok(Response::default().add_attribute("purged", purged.to_string()))
+}
+
+/// the meta of a note given a new key
+fn relocate(meta: NoteMeta, to: NotePk) -> NoteMeta {
+    let (topic, sub_topic, name) = to;
+    NoteMeta {
+        topic: topic.to_string(),
+        sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
+        ..meta
+    }
+}
+
+/// re-keys a note and everything kept under it, returning how many entries were rewritten.
+/// bodies are shared by hash, so stay put
+fn move_note(store: &mut dyn Storage, from: NotePk, to: NotePk) -> Result<usize, ContractError> {
+    let (topic, sub_topic, _) = to;
+    assert_no_upload(store, from)?;
+    claim_name(store, to)?;
+    let meta = notes().load(store, from)?;
+    notes().remove(store, from)?;
+    notes().save(store, to, &relocate(meta, to))?;
+    let mut writes = 1 + move_note_data(store, from, to)?;
+    writes += move_draft(store, from, to)?;
+    writes += redirect(store, from, to)?;
+
+    TOPICSMAP.save(store, topic.to_string(), &topic.to_string())?;
+    topic_subtopics().save(
+        store,
+        (topic, sub_topic),
+        &SubTopicKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+        },
+    )?;
+    Ok(writes)
+}
+
+/// re-keys a note in the trash, along with what it left under its key
+fn move_trashed(store: &mut dyn Storage, from: NotePk, to: NotePk) -> Result<usize, ContractError> {
+    let (topic, sub_topic, name) = to;
+    if notes().has(store, to) || aliases().has(store, to) || trash().has(store, to) {
+        return Err(ContractError::NameTaken(NoteKey::from((topic, sub_topic, name)).to_string()));
+    }
+    let entry = trash().load(store, from)?;
+    trash().remove(store, from)?;
+    trash().save(
+        store,
+        to,
+        &TrashEntry {
+            meta: relocate(entry.meta, to),
+            ..entry
+        },
+    )?;
+    Ok(1 + move_note_data(store, from, to)? + redirect(store, from, to)?)
+}
+
+/// re-keys the draft kept under a note's key, if there is one. its body stays put
+fn move_draft(store: &mut dyn Storage, from: NotePk, to: NotePk) -> Result<usize, ContractError> {
+    let Some(draft) = DRAFTS.may_load(store, from)? else {
+        return Ok(0);
+    };
+    let (topic, sub_topic, name) = to;
+    if DRAFTS.has(store, to) || aliases().has(store, to) {
+        return Err(ContractError::NameTaken(NoteKey::from((topic, sub_topic, name)).to_string()));
+    }
+    DRAFTS.remove(store, from);
+    DRAFTS.save(store, to, &relocate(draft, to))?;
+    Ok(1)
+}
+
+/// re-keys the history and items kept under a note's key
+fn move_note_data(store: &mut dyn Storage, from: NotePk, to: NotePk) -> StdResult<usize> {
+    let history = NOTE_HISTORY
+        .prefix(from)
+        .range(store, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(u64, NoteMeta)>>>()?;
+    let mut writes = history.len();
+    for (revision, meta) in history {
+        NOTE_HISTORY.remove(store, (from, revision));
+        NOTE_HISTORY.save(store, (to, revision), &relocate(meta, to))?;
+    }
+    if let Some(list) = LISTS.may_load(store, from)? {
+        for index in list.first..list.next {
+            let item = LIST_ITEMS.load(store, (from, index))?;
+            LIST_ITEMS.remove(store, (from, index));
+            LIST_ITEMS.save(store, (to, index), &item)?;
+        }
+        LISTS.remove(store, from);
+        LISTS.save(store, to, &list)?;
+        writes += 1 + (list.next - list.first) as usize;
+    }
+    let fields = MAP_FIELDS
+        .prefix(from)
+        .range(store, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, NoteField)>>>()?;
+    writes += fields.len();
+    for (field, value) in fields {
+        MAP_FIELDS.remove(store, (from, &field));
+        MAP_FIELDS.save(store, (to, &field), &value)?;
+    }
+    Ok(writes)
+}
+
+fn move_alias(store: &mut dyn Storage, from: NotePk, to: NotePk) -> Result<usize, ContractError> {
+    let (topic, sub_topic, name) = to;
+    if notes().has(store, to) || aliases().has(store, to) {
+        return Err(ContractError::NameTaken(NoteKey::from((topic, sub_topic, name)).to_string()));
+    }
+    let alias = aliases().load(store, from)?;
+    aliases().remove(store, from)?;
+    aliases().save(store, to, &alias)?;
+    Ok(1 + redirect(store, from, to)?)
+}
+
+/// points the aliases and note links that reach `from` at `to`, returning how many were
+/// rewritten. links kept in history, drafts and the trash are left as they were
+fn redirect(store: &mut dyn Storage, from: NotePk, to: NotePk) -> StdResult<usize> {
+    let (topic, sub_topic, name) = to;
+    let target = NoteKey {
         topic: topic.to_string(),
         sub_topic: sub_topic.to_string(),
+        name: name.to_string(),
     };
+    let (from_topic, from_sub_topic, from_name) = from;
+    let alias_keys = aliases()
+        .idx
+        .target
+        .prefix((from_topic.to_string(), from_sub_topic.to_string(), from_name.to_string()))
+        .keys(store, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, String, String)>>>()?;
+    for (topic, sub_topic, name) in &alias_keys {
+        let pk = (topic.as_str(), sub_topic.as_str(), name.as_str());
+        let alias = aliases().load(store, pk)?;
+        aliases().save(
+            store,
+            pk,
+            &NoteAlias {
+                target: target.clone(),
+                ..alias
+            },
+        )?;
+    }
+
+    let linking = notes()
+        .idx
+        .link
+        .idx
+        .prefix(from)
+        .keys(store, None, None, Order::Ascending)
+        .map(|pk| pk.and_then(NotePk::from_vec))
+        .collect::<StdResult<Vec<(String, String, String)>>>()?;
+    for (topic, sub_topic, name) in &linking {
+        let pk = (topic.as_str(), sub_topic.as_str(), name.as_str());
+        let mut meta = notes().load(store, pk)?;
+        let already_linked = meta.links.contains(&target);
+        meta.links.retain(|link| {
+            (link.topic.as_str(), link.sub_topic.as_str(), link.name.as_str()) != from
+        });
+        if !already_linked {
+            meta.links.push(target.clone());
+        }
+        notes().save(store, pk, &meta)?;
+    }
+    Ok(alias_keys.len() + linking.len())
+}
+
+/// applies `step` to keys in turn until the batch has rewritten `MAX_BATCH_WRITES` entries, and
+/// returns how many keys it took
+fn run_batch<K>(
+    keys: &[K],
+    writes: &mut usize,
+    mut step: impl FnMut(&K) -> Result<usize, ContractError>,
+) -> Result<usize, ContractError> {
+    let mut taken = 0;
+    for key in keys {
+        if *writes >= MAX_BATCH_WRITES {
+            break;
+        }
+        *writes += step(key)?;
+        taken += 1;
+    }
+    Ok(taken)
+}
+
+pub fn exec_move_note(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    to: NoteKey,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let from = (topic, sub_topic, name);
+    if from == (to.topic.as_str(), to.sub_topic.as_str(), to.name.as_str()) {
+        return Err(ContractError::MoveToSelf(to.to_string()));
+    }
+    move_note(deps.storage, from, (&to.topic, &to.sub_topic, &to.name))?;
+    Ok(Response::default())
+}
+
+pub fn exec_rename_sub_topic(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    new_sub_topic: &str,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    if sub_topic == new_sub_topic {
+        return Err(ContractError::MoveToSelf(format!("{:?}/{:?}", topic, sub_topic)));
+    }
+    topic_subtopics().load(deps.storage, (topic, sub_topic))?;
+    // uploads stay under the key they were begun on, so have to finish or be aborted first
+    if let Some(name) = UPLOADS
+        .prefix((topic, sub_topic))
+        .keys(deps.storage, None, None, Order::Ascending)
+        .next()
+        .transpose()?
+    {
+        return Err(ContractError::UploadPending(
+            NoteKey::from((topic, sub_topic, name.as_str())).to_string(),
+        ));
+    }
+    let mut budget = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_BATCH_LIMIT) as usize;
+    let mut writes = 0;
+
+    // notes, then aliases, trashed notes and drafts, from what is left of the batch
+    let (names, mut done) = take_batch(
+        notes().prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+        budget,
+    )?;
+    let moved_notes = run_batch(&names, &mut writes, |name| {
+        move_note(deps.storage, (topic, sub_topic, name), (topic, new_sub_topic, name))
+    })?;
+    done &= moved_notes == names.len();
+    budget -= moved_notes;
+    let mut moved_aliases = 0;
+    if done {
+        let (names, aliases_done) = take_batch(
+            aliases().prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        moved_aliases = run_batch(&names, &mut writes, |name| {
+            move_alias(deps.storage, (topic, sub_topic, name), (topic, new_sub_topic, name))
+        })?;
+        done = aliases_done && moved_aliases == names.len();
+        budget -= moved_aliases;
+    }
+    let mut moved_trash = 0;
+    if done {
+        let (names, trash_done) = take_batch(
+            trash().prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        moved_trash = run_batch(&names, &mut writes, |name| {
+            move_trashed(deps.storage, (topic, sub_topic, name), (topic, new_sub_topic, name))
+        })?;
+        done = trash_done && moved_trash == names.len();
+        budget -= moved_trash;
+    }
+    // the drafts of moved notes went with them, what is left belongs to new or trashed notes
+    let mut moved_drafts = 0;
+    if done {
+        let (names, drafts_done) = take_batch(
+            DRAFTS.prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        moved_drafts = run_batch(&names, &mut writes, |name| {
+            move_draft(deps.storage, (topic, sub_topic, name), (topic, new_sub_topic, name))
+        })?;
+        done = drafts_done && moved_drafts == names.len();
+    }
+    if done {
+        move_sub_topic(deps.storage, topic, sub_topic, topic, new_sub_topic)?;
+    }
+
+    Ok(Response::default()
+        .add_attribute("moved_notes", moved_notes.to_string())
+        .add_attribute("done", done.to_string())
+        .add_attribute("moved_aliases", moved_aliases.to_string())
+        .add_attribute("moved_trash", moved_trash.to_string())
+        .add_attribute("moved_drafts", moved_drafts.to_string()))
+}
+
+/// moves the subtopic entry and its info, once its notes have gone
+fn move_sub_topic(
+    store: &mut dyn Storage,
+    topic: &str,
+    sub_topic: &str,
+    new_topic: &str,
+    new_sub_topic: &str,
+) -> Result<(), ContractError> {
+    topic_subtopics().remove(store, (topic, sub_topic))?;
+    topic_subtopics().save(
+        store,
+        (new_topic, new_sub_topic),
+        &SubTopicKey {
+            topic: new_topic.to_string(),
+            sub_topic: new_sub_topic.to_string(),
+        },
+    )?;
+    if let Some(info) = SUBTOPIC_INFO.may_load(store, (topic, sub_topic))? {
+        SUBTOPIC_INFO.remove(store, (topic, sub_topic));
+        if !SUBTOPIC_INFO.has(store, (new_topic, new_sub_topic)) {
+            SUBTOPIC_INFO.save(store, (new_topic, new_sub_topic), &info)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn exec_rename_topic(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    new_topic: &str,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    if topic == new_topic {
+        return Err(ContractError::MoveToSelf(topic.to_string()));
+    }
+    TOPICSMAP.load(deps.storage, topic.to_string())?;
+    if let Some((sub_topic, name)) = UPLOADS
+        .sub_prefix(topic)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .next()
+        .transpose()?
+    {
+        return Err(ContractError::UploadPending(
+            NoteKey::from((topic, sub_topic.as_str(), name.as_str())).to_string(),
+        ));
+    }
+    let mut budget = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_BATCH_LIMIT) as usize;
+    let mut writes = 0;
+
+    // notes, then aliases, trashed notes and drafts, then the subtopics they leave empty
+    let (keys, mut done) = take_batch(
+        notes().sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+        budget,
+    )?;
+    let moved_notes = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+        move_note(deps.storage, (topic, sub_topic, name), (new_topic, sub_topic, name))
+    })?;
+    done &= moved_notes == keys.len();
+    budget -= moved_notes;
+    let mut moved_aliases = 0;
+    if done {
+        let (keys, aliases_done) = take_batch(
+            aliases().sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        moved_aliases = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+            move_alias(deps.storage, (topic, sub_topic, name), (new_topic, sub_topic, name))
+        })?;
+        done = aliases_done && moved_aliases == keys.len();
+        budget -= moved_aliases;
+    }
+    let mut moved_trash = 0;
+    if done {
+        let (keys, trash_done) = take_batch(
+            trash().sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        moved_trash = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+            move_trashed(deps.storage, (topic, sub_topic, name), (new_topic, sub_topic, name))
+        })?;
+        done = trash_done && moved_trash == keys.len();
+        budget -= moved_trash;
+    }
+    let mut moved_drafts = 0;
+    if done {
+        let (keys, drafts_done) = take_batch(
+            DRAFTS.sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        moved_drafts = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+            move_draft(deps.storage, (topic, sub_topic, name), (new_topic, sub_topic, name))
+        })?;
+        done = drafts_done && moved_drafts == keys.len();
+        budget -= moved_drafts;
+    }
+    let mut moved_sub_topics = 0;
+    if done {
+        let (sub_topics, sub_topics_done) = take_batch(
+            topic_subtopics().prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        moved_sub_topics = run_batch(&sub_topics, &mut writes, |sub_topic| {
+            move_sub_topic(deps.storage, topic, sub_topic, new_topic, sub_topic)?;
+            Ok(1)
+        })?;
+        done = sub_topics_done && moved_sub_topics == sub_topics.len();
+    }
+    if done {
+        TOPICSMAP.remove(deps.storage, topic.to_string());
+        TOPICSMAP.save(deps.storage, new_topic.to_string(), &new_topic.to_string())?;
+        if let Some(info) = TOPIC_INFO.may_load(deps.storage, topic)? {
+            TOPIC_INFO.remove(deps.storage, topic);
+            if !TOPIC_INFO.has(deps.storage, new_topic) {
+                TOPIC_INFO.save(deps.storage, new_topic, &info)?;
+            }
+        }
+        if let Some(size) = TOPIC_NOTE_SIZE.may_load(deps.storage, topic)? {
+            TOPIC_NOTE_SIZE.remove(deps.storage, topic);
+            if !TOPIC_NOTE_SIZE.has(deps.storage, new_topic) {
+                TOPIC_NOTE_SIZE.save(deps.storage, new_topic, &size)?;
+            }
+        }
+    }
+
+    Ok(Response::default()
+        .add_attribute("moved_notes", moved_notes.to_string())
+        .add_attribute("moved_sub_topics", moved_sub_topics.to_string())
+        .add_attribute("done", done.to_string())
+        .add_attribute("moved_aliases", moved_aliases.to_string())
+        .add_attribute("moved_trash", moved_trash.to_string())
+        .add_attribute("moved_drafts", moved_drafts.to_string()))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exec_copy_sub_topic(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    topic: &str,
+    from: &str,
+    to: &str,
+    overwrite: bool,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    if from == to {
+        return Err(ContractError::MoveToSelf(format!("{:?}/{:?}", topic, from)));
+    }
+    topic_subtopics().load(deps.storage, (topic, from))?;
+    let limit_amt = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_BATCH_LIMIT) as usize;
+    let now = NoteStamp::from(&env.block);
+
+    if start_after.is_none() {
+        topic_subtopics().save(
+            deps.storage,
+            (topic, to),
+            &SubTopicKey {
+                topic: topic.to_string(),
+                sub_topic: to.to_string(),
+            },
+        )?;
+        if let Some(info) = SUBTOPIC_INFO.may_load(deps.storage, (topic, from))? {
+            if !SUBTOPIC_INFO.has(deps.storage, (topic, to)) {
+                SUBTOPIC_INFO.save(deps.storage, (topic, to), &info)?;
+            }
+        }
+    }
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let (sources, mut done) = take_batch(
+        notes().prefix((topic, from)).range(deps.storage, start, None, Order::Ascending),
+        limit_amt,
+    )?;
+
+    let mut writes = 0;
+    let mut copied = 0;
+    let mut skipped = 0;
+    let mut removed_drafts = 0;
+    let mut last = String::new();
+    for (name, meta) in sources {
+        if writes >= MAX_BATCH_WRITES {
+            done = false;
+            break;
+        }
+        let pk = (topic, to, name.as_str());
+        if aliases().has(deps.storage, pk) {
+            return Err(ContractError::NameTaken(NoteKey::from(pk).to_string()));
+        }
+        if notes().has(deps.storage, pk) {
+            if !overwrite {
+                skipped += 1;
+                last = name;
+                continue;
+            }
+            // the copy starts afresh, so the note it replaces goes with its history and draft
+            removed_drafts += DRAFTS.has(deps.storage, pk) as usize;
+            writes += remove_note(deps.storage, pk)?;
+        }
+        writes += copy_note(deps.storage, sender, &now, (topic, from, &name), pk, meta)?;
+        copied += 1;
+        last = name;
+    }
+
+    Ok(Response::default()
+        .add_attribute("copied_notes", copied.to_string())
+        .add_attribute("skipped_notes", skipped.to_string())
+        .add_attribute("done", done.to_string())
+        .add_attribute("last", last)
+        .add_attribute("removed_drafts", removed_drafts.to_string()))
+}
+
+/// saves a fresh copy of the note, with its items, under a key that is free. returns how many
+/// entries were written
+fn copy_note(
+    store: &mut dyn Storage,
+    sender: &Addr,
+    now: &NoteStamp,
+    from: NotePk,
+    to: NotePk,
+    meta: NoteMeta,
+) -> Result<usize, ContractError> {
+    let (topic, sub_topic, name) = to;
+    claim_name(store, to)?;
+    let mut writes = 1;
+    retain_body(store, &meta.hash)?;
+    notes().save(
+        store,
+        to,
+        &NoteMeta {
+            writer: sender.clone(),
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+            name: name.to_string(),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            revision: 1,
+            ..meta
+        },
+    )?;
+    if let Some(list) = LISTS.may_load(store, from)? {
+        for index in list.first..list.next {
+            let item = LIST_ITEMS.load(store, (from, index))?;
+            LIST_ITEMS.save(store, (to, index), &item)?;
+        }
+        LISTS.save(store, to, &list)?;
+        writes += 1 + (list.next - list.first) as usize;
+    }
+    let fields = MAP_FIELDS
+        .prefix(from)
+        .range(store, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, NoteField)>>>()?;
+    writes += fields.len();
+    for (field, value) in fields {
+        MAP_FIELDS.save(store, (to, &field), &value)?;
+    }
+    Ok(writes)
+}
+
+pub fn exec_rm_sub_topic(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let mut budget = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_BATCH_LIMIT) as usize;
+    let mut writes = 0;
+
+    // notes, then aliases, trashed notes and drafts, from what is left of the batch
+    let (names, mut done) = take_batch(
+        notes().prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+        budget,
+    )?;
+    // drafts of the notes go with them, and are counted with the others
+    let mut removed_drafts = 0;
+    let removed_notes = run_batch(&names, &mut writes, |name| {
+        removed_drafts += DRAFTS.has(deps.storage, (topic, sub_topic, name)) as usize;
+        Ok(remove_note(deps.storage, (topic, sub_topic, name))?)
+    })?;
+    done &= removed_notes == names.len();
+    budget -= removed_notes;
+    let mut removed_aliases = 0;
+    if done {
+        let (names, aliases_done) = take_batch(
+            aliases().prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        removed_aliases = run_batch(&names, &mut writes, |name| {
+            aliases().remove(deps.storage, (topic, sub_topic, name))?;
+            Ok(1)
+        })?;
+        done = aliases_done && removed_aliases == names.len();
+        budget -= removed_aliases;
+    }
+    let mut removed_trash = 0;
+    if done {
+        let (names, trash_done) = take_batch(
+            trash().prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        removed_trash = run_batch(&names, &mut writes, |name| {
+            Ok(purge_trashed(deps.storage, (topic, sub_topic, name))?)
+        })?;
+        done = trash_done && removed_trash == names.len();
+        budget -= removed_trash;
+    }
+    if done {
+        let (names, drafts_done) = take_batch(
+            DRAFTS.prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        let cleared = run_batch(&names, &mut writes, |name| {
+            Ok(clear_draft(deps.storage, (topic, sub_topic, name))?)
+        })?;
+        done = drafts_done && cleared == names.len();
+        budget -= cleared;
+        removed_drafts += cleared;
+    }
+    // uploads to names that have no note yet
+    let mut removed_uploads = 0;
+    if done {
+        let (names, uploads_done) = take_batch(
+            UPLOADS.prefix((topic, sub_topic)).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        removed_uploads = run_batch(&names, &mut writes, |name| {
+            Ok(clear_upload(deps.storage, (topic, sub_topic, name))?)
+        })?;
+        done = uploads_done && removed_uploads == names.len();
+    }
+    if done {
+        topic_subtopics().remove(deps.storage, (topic, sub_topic))?;
+        SUBTOPIC_INFO.remove(deps.storage, (topic, sub_topic));
+    }
+
+    Ok(Response::default()
+        .add_attribute("removed_notes", removed_notes.to_string())
+        .add_attribute("done", done.to_string())
+        .add_attribute("removed_aliases", removed_aliases.to_string())
+        .add_attribute("removed_trash", removed_trash.to_string())
+        .add_attribute("removed_drafts", removed_drafts.to_string())
+        .add_attribute("removed_uploads", removed_uploads.to_string()))
+}
+pub fn exec_rm_topic(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let mut budget = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_BATCH_LIMIT) as usize;
+    let mut writes = 0;
 
-    topic_subtopics().remove(deps.storage, subtopic_key.to_string())?;
+    // notes go first, including any left behind by subtopics removed without them
+    let (keys, mut done) = take_batch(
+        notes().sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+        budget,
+    )?;
+    // drafts of the notes go with them, and are counted with the others
+    let mut removed_drafts = 0;
+    let removed_notes = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+        removed_drafts += DRAFTS.has(deps.storage, (topic, sub_topic, name)) as usize;
+        Ok(remove_note(deps.storage, (topic, sub_topic, name))?)
+    })?;
+    done &= removed_notes == keys.len();
+    budget -= removed_notes;
+    let mut removed_aliases = 0;
+    if done {
+        let (keys, aliases_done) = take_batch(
+            aliases().sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        removed_aliases = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+            aliases().remove(deps.storage, (topic, sub_topic, name))?;
+            Ok(1)
+        })?;
+        done = aliases_done && removed_aliases == keys.len();
+        budget -= removed_aliases;
+    }
+    let mut removed_trash = 0;
+    if done {
+        let (keys, trash_done) = take_batch(
+            trash().sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        removed_trash = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+            Ok(purge_trashed(deps.storage, (topic, sub_topic, name))?)
+        })?;
+        done = trash_done && removed_trash == keys.len();
+        budget -= removed_trash;
+    }
+    if done {
+        let (keys, drafts_done) = take_batch(
+            DRAFTS.sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        let cleared = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+            Ok(clear_draft(deps.storage, (topic, sub_topic, name))?)
+        })?;
+        done = drafts_done && cleared == keys.len();
+        budget -= cleared;
+        removed_drafts += cleared;
+    }
+    let mut removed_uploads = 0;
+    if done {
+        let (keys, uploads_done) = take_batch(
+            UPLOADS.sub_prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        removed_uploads = run_batch(&keys, &mut writes, |(sub_topic, name)| {
+            Ok(clear_upload(deps.storage, (topic, sub_topic, name))?)
+        })?;
+        done = uploads_done && removed_uploads == keys.len();
+        budget -= removed_uploads;
+    }
+    let mut removed_sub_topics = 0;
+    if done {
+        let (sub_topics, sub_topics_done) = take_batch(
+            topic_subtopics().prefix(topic).keys(deps.storage, None, None, Order::Ascending),
+            budget,
+        )?;
+        removed_sub_topics = run_batch(&sub_topics, &mut writes, |sub_topic| {
+            topic_subtopics().remove(deps.storage, (topic, sub_topic))?;
+            SUBTOPIC_INFO.remove(deps.storage, (topic, sub_topic));
+            Ok(2)
+        })?;
+        done = sub_topics_done && removed_sub_topics == sub_topics.len();
+    }
+    if done {
+        TOPICSMAP.remove(deps.storage, topic.to_string());
+        TOPIC_INFO.remove(deps.storage, topic);
+        TOPIC_NOTE_SIZE.remove(deps.storage, topic);
+    }
 
+    Ok(Response::default()
+        .add_attribute("removed_notes", removed_notes.to_string())
+        .add_attribute("removed_sub_topics", removed_sub_topics.to_string())
+        .add_attribute("done", done.to_string())
+        .add_attribute("removed_aliases", removed_aliases.to_string())
+        .add_attribute("removed_trash", removed_trash.to_string())
+        .add_attribute("removed_drafts", removed_drafts.to_string())
+        .add_attribute("removed_uploads", removed_uploads.to_string()))
+}
+
+pub fn exec_prune_expired(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    let limit_amt = limit.unwrap_or(DEFAULT_BATCH_LIMIT).min(MAX_BATCH_LIMIT) as usize;
+
+    // everything indexed at or before the current block time
+    let expired = notes()
+        .idx
+        .expiry
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::exclusive((env.block.time.nanos() + 1, ("", "", "")))),
+            Order::Ascending,
+        )
+        .take(limit_amt)
+        .map(|item| item.map(|(k, _)| k))
+        .collect::<StdResult<Vec<(String, String, String)>>>()?;
+    let mut writes = 0;
+    let mut removed_drafts = 0;
+    let pruned = run_batch(&expired, &mut writes, |(topic, sub_topic, name)| {
+        let pk = (topic.as_str(), sub_topic.as_str(), name.as_str());
+        removed_drafts += DRAFTS.has(deps.storage, pk) as usize;
+        Ok(remove_note(deps.storage, pk)?)
+    })?;
+
+    Ok(Response::default()
+        .add_attribute("pruned", pruned.to_string())
+        .add_attribute("removed_drafts", removed_drafts.to_string()))
+}
+
+pub fn exec_set_topic_info(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    info: &TopicInfo,
+) -> Result<Response, ContractError> {
+    assert_is_writer(deps.storage, sender)?;
+    validate_topic_info(info)?;
+    TOPICSMAP.load(deps.storage, topic.to_string())?;
+    TOPIC_INFO.save(deps.storage, topic, info)?;
     Ok(Response::default())
 }
-pub fn exec_rm_topic(deps: DepsMut, sender: &Addr, topic: &str) -> Result<Response, ContractError> {
+
+pub fn exec_set_sub_topic_info(
+    deps: DepsMut,
+    sender: &Addr,
+    topic: &str,
+    sub_topic: &str,
+    info: &TopicInfo,
+) -> Result<Response, ContractError> {
     assert_is_writer(deps.storage, sender)?;
+    validate_topic_info(info)?;
+    topic_subtopics().load(deps.storage, (topic, sub_topic))?;
+    SUBTOPIC_INFO.save(deps.storage, (topic, sub_topic), info)?;
+    Ok(Response::default())
+}
 
-    TOPICSMAP.remove(deps.storage, topic.to_string());
+pub fn exec_update_config(
+    deps: DepsMut,
+    history_depth: Option<u32>,
+    max_note_size: Option<u32>,
+    trash_retention: Option<u64>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if let Some(history_depth) = history_depth {
+        assert_history_depth(history_depth)?;
+        config.history_depth = history_depth;
+    }
+    if let Some(max_note_size) = max_note_size {
+        assert_max_note_size(max_note_size)?;
+        config.max_note_size = max_note_size;
+    }
+    if let Some(trash_retention) = trash_retention {
+        config.trash_retention = trash_retention;
+    }
+    CONFIG.save(deps.storage, &config)?;
+    Ok(Response::default())
+}
+
+pub fn exec_set_topic_note_size(
+    deps: DepsMut,
+    topic: &str,
+    max_note_size: Option<u32>,
+) -> Result<Response, ContractError> {
+    match max_note_size {
+        Some(max_note_size) => {
+            assert_max_note_size(max_note_size)?;
+            TOPIC_NOTE_SIZE.save(deps.storage, topic, &max_note_size)?;
+        },
+        None => TOPIC_NOTE_SIZE.remove(deps.storage, topic),
+    }
     Ok(Response::default())
 }
 
@@ -2,21 +2,41 @@ use cosmwasm_std::{
     entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
 };
 use error::ContractError;
-use pfc_notes::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use pfc_notes::{Config, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
 use crate::{
     execute::{
-        exec_add_note, exec_add_writer, exec_rm_note, exec_rm_sub_topic, exec_rm_topic,
-        exec_rm_writer,
+        assert_history_depth, assert_max_note_size, exec_abort_upload, exec_add_note,
+        exec_add_writer, exec_append_to_note, exec_begin_upload, exec_copy_sub_topic,
+        exec_decrement_note, exec_discard_draft, exec_finalize_upload, exec_increment_note,
+        exec_move_note, exec_patch_note, exec_prune_expired, exec_publish_note, exec_purge_trash,
+        exec_remove_alias, exec_remove_note_field, exec_rename_sub_topic, exec_rename_topic,
+        exec_restore_note, exec_revert_note, exec_rm_note, exec_rm_sub_topic, exec_rm_topic,
+        exec_rm_writer, exec_set_alias, exec_set_note_field, exec_set_sub_topic_info,
+        exec_set_topic_info, exec_set_topic_note_size, exec_trash_note, exec_update_config,
+        exec_upload_chunk, max_note_size, resolve_note_type, NoteOptions,
+    },
+    migrate::{exec_migrate_storage, migrate_config},
+    query::{
+        query_alias, query_backlinks, query_dangling_links, query_drafts, query_entries,
+        query_list_items, query_note, query_note_at_revision, query_note_chunk, query_note_fields,
+        query_note_hash, query_note_history, query_notes_by_hash, query_notes_by_tag,
+        query_removal_status, query_sub_topics, query_topics, query_trash, query_upload,
+        query_writer,
+    },
+    state::{
+        CONFIG, DEFAULT_HISTORY_DEPTH, DEFAULT_MAX_NOTE_SIZE, DEFAULT_TRASH_RETENTION, WRITER,
     },
-    query::{query_entries, query_note, query_sub_topics, query_topics, query_writer},
-    state::WRITER,
 };
 
 mod error;
 mod execute;
+mod json;
+mod migrate;
+mod patch;
 mod query;
 mod state;
+mod validation;
 
 pub const CONTRACT_NAME: &str = "crates.io:pfc-notes-contract";
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -27,9 +47,21 @@ pub fn instantiate(
     _env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     cw_ownable::initialize_owner(deps.storage, deps.api, Some(&msg.owner))?;
+    let history_depth = msg.history_depth.unwrap_or(DEFAULT_HISTORY_DEPTH);
+    assert_history_depth(history_depth)?;
+    let max_note_size = msg.max_note_size.unwrap_or(DEFAULT_MAX_NOTE_SIZE);
+    assert_max_note_size(max_note_size)?;
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            history_depth,
+            max_note_size,
+            trash_retention: msg.trash_retention.unwrap_or(DEFAULT_TRASH_RETENTION),
+        },
+    )?;
     for writer in msg.writers {
         let addr = deps.api.addr_validate(&writer.address)?;
         WRITER.save(deps.storage, addr, &writer.name)?
@@ -68,24 +100,305 @@ pub fn execute(
             name,
             note_type,
             note,
-        } => exec_add_note(deps, &info.sender, &topic, &sub_topic, &name, note_type, &note),
+            expires_at,
+            ttl_seconds,
+            expected_revision,
+            tags,
+            compression,
+            links,
+            draft,
+        } => {
+            let note_type = resolve_note_type(deps.storage, &topic, &sub_topic, note_type)?;
+            exec_add_note(
+                deps,
+                &env,
+                &info.sender,
+                &topic,
+                &sub_topic,
+                &name,
+                note_type,
+                &note,
+                NoteOptions {
+                    expires_at,
+                    ttl_seconds,
+                    expected_revision,
+                    tags,
+                    compression,
+                    links,
+                    draft: draft.unwrap_or_default(),
+                },
+            )
+        },
+        ExecuteMsg::PublishNote {
+            topic,
+            sub_topic,
+            name,
+            expected_revision,
+        } => exec_publish_note(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &sub_topic,
+            &name,
+            expected_revision,
+        ),
+        ExecuteMsg::DiscardDraft {
+            topic,
+            sub_topic,
+            name,
+        } => exec_discard_draft(deps, &info.sender, &topic, &sub_topic, &name),
+        ExecuteMsg::AppendToNote {
+            topic,
+            sub_topic,
+            name,
+            note_type,
+            item,
+            capacity,
+        } => exec_append_to_note(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &sub_topic,
+            &name,
+            note_type,
+            &item,
+            capacity,
+        ),
+        ExecuteMsg::SetNoteField {
+            topic,
+            sub_topic,
+            name,
+            note_type,
+            field,
+            value,
+        } => exec_set_note_field(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &sub_topic,
+            &name,
+            note_type,
+            &field,
+            &value,
+        ),
+        ExecuteMsg::RemoveNoteField {
+            topic,
+            sub_topic,
+            name,
+            field,
+        } => exec_remove_note_field(deps, &env, &info.sender, &topic, &sub_topic, &name, &field),
+        ExecuteMsg::SetAlias {
+            topic,
+            sub_topic,
+            name,
+            target,
+        } => exec_set_alias(deps, &env, &info.sender, &topic, &sub_topic, &name, target),
+        ExecuteMsg::RemoveAlias {
+            topic,
+            sub_topic,
+            name,
+        } => exec_remove_alias(deps, &info.sender, &topic, &sub_topic, &name),
+        ExecuteMsg::IncrementNote {
+            topic,
+            sub_topic,
+            name,
+            by,
+        } => exec_increment_note(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &sub_topic,
+            &name,
+            by.unwrap_or(1),
+        ),
+        ExecuteMsg::DecrementNote {
+            topic,
+            sub_topic,
+            name,
+            by,
+        } => exec_decrement_note(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &sub_topic,
+            &name,
+            by.unwrap_or(1),
+        ),
+        ExecuteMsg::PatchNote {
+            topic,
+            sub_topic,
+            name,
+            patch,
+            expected_revision,
+        } => exec_patch_note(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &sub_topic,
+            &name,
+            &patch,
+            expected_revision,
+        ),
         ExecuteMsg::RemoveNote {
             topic,
             sub_topic,
             name,
-        } => exec_rm_note(deps, &info.sender, &topic, &sub_topic, &name),
+            expected_revision,
+            soft,
+        } => {
+            if soft.unwrap_or_default() {
+                exec_trash_note(
+                    deps,
+                    &env,
+                    &info.sender,
+                    &topic,
+                    &sub_topic,
+                    &name,
+                    expected_revision,
+                )
+            } else {
+                exec_rm_note(deps, &info.sender, &topic, &sub_topic, &name, expected_revision)
+            }
+        },
+        ExecuteMsg::RestoreNote {
+            topic,
+            sub_topic,
+            name,
+        } => exec_restore_note(deps, &info.sender, &topic, &sub_topic, &name),
+        ExecuteMsg::PurgeTrash {
+            limit,
+        } => exec_purge_trash(deps, &env, &info.sender, limit),
+        ExecuteMsg::MoveNote {
+            topic,
+            sub_topic,
+            name,
+            to,
+        } => exec_move_note(deps, &info.sender, &topic, &sub_topic, &name, to),
+        ExecuteMsg::RenameSubTopic {
+            topic,
+            sub_topic,
+            new_sub_topic,
+            limit,
+        } => exec_rename_sub_topic(deps, &info.sender, &topic, &sub_topic, &new_sub_topic, limit),
+        ExecuteMsg::RenameTopic {
+            topic,
+            new_topic,
+            limit,
+        } => exec_rename_topic(deps, &info.sender, &topic, &new_topic, limit),
+        ExecuteMsg::CopySubTopic {
+            topic,
+            from,
+            to,
+            overwrite,
+            start_after,
+            limit,
+        } => exec_copy_sub_topic(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &from,
+            &to,
+            overwrite,
+            start_after,
+            limit,
+        ),
         ExecuteMsg::RemoveSubTopic {
             topic,
             sub_topic,
-        } => exec_rm_sub_topic(deps, &info.sender, &topic, &sub_topic),
+            limit,
+        } => exec_rm_sub_topic(deps, &info.sender, &topic, &sub_topic, limit),
         ExecuteMsg::RemoveTopic {
             topic,
-        } => exec_rm_topic(deps, &info.sender, &topic),
+            limit,
+        } => exec_rm_topic(deps, &info.sender, &topic, limit),
+        ExecuteMsg::PruneExpired {
+            limit,
+        } => exec_prune_expired(deps, &env, &info.sender, limit),
+        ExecuteMsg::RevertNote {
+            topic,
+            sub_topic,
+            name,
+            revision,
+        } => exec_revert_note(deps, &env, &info.sender, &topic, &sub_topic, &name, revision),
+        ExecuteMsg::BeginUpload {
+            topic,
+            sub_topic,
+            name,
+            note_type,
+            total_chunks,
+            total_size,
+            sha256,
+        } => exec_begin_upload(
+            deps,
+            &env,
+            &info.sender,
+            &topic,
+            &sub_topic,
+            &name,
+            note_type,
+            total_chunks,
+            total_size,
+            &sha256,
+        ),
+        ExecuteMsg::UploadChunk {
+            topic,
+            sub_topic,
+            name,
+            index,
+            data,
+        } => exec_upload_chunk(deps, &info.sender, &topic, &sub_topic, &name, index, &data),
+        ExecuteMsg::AbortUpload {
+            topic,
+            sub_topic,
+            name,
+        } => exec_abort_upload(deps, &info.sender, &topic, &sub_topic, &name),
+        ExecuteMsg::FinalizeUpload {
+            topic,
+            sub_topic,
+            name,
+        } => exec_finalize_upload(deps, &env, &info.sender, &topic, &sub_topic, &name),
+        ExecuteMsg::SetTopicInfo {
+            topic,
+            info: topic_info,
+        } => exec_set_topic_info(deps, &info.sender, &topic, &topic_info),
+        ExecuteMsg::SetSubTopicInfo {
+            topic,
+            sub_topic,
+            info: topic_info,
+        } => exec_set_sub_topic_info(deps, &info.sender, &topic, &sub_topic, &topic_info),
+        ExecuteMsg::MigrateStorage {
+            limit,
+        } => {
+            cw_ownable::assert_owner(deps.storage, &info.sender)?;
+            exec_migrate_storage(deps.storage, &env.block, limit)
+        },
+        ExecuteMsg::UpdateConfig {
+            history_depth,
+            max_note_size,
+            trash_retention,
+        } => {
+            cw_ownable::assert_owner(deps.storage, &info.sender)?;
+            exec_update_config(deps, history_depth, max_note_size, trash_retention)
+        },
+        ExecuteMsg::SetTopicNoteSize {
+            topic,
+            max_note_size,
+        } => {
+            cw_ownable::assert_owner(deps.storage, &info.sender)?;
+            exec_set_topic_note_size(deps, &topic, max_note_size)
+        },
     }
 }
 
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Ownership {} => {
             let ownership = cw_ownable::get_ownership(deps.storage)?;
@@ -109,18 +422,106 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             sub_topic,
             start_after,
             limit,
-        } => to_binary(&query_entries(deps, &topic, &sub_topic, start_after, limit)?),
+        } => to_binary(&query_entries(deps, &env, &topic, &sub_topic, start_after, limit)?),
         QueryMsg::Note {
             topic,
             sub_topic,
             name,
-        } => to_binary(&query_note(deps, &topic, &sub_topic, &name)?),
+        } => to_binary(&query_note(deps, &env, &topic, &sub_topic, &name)?),
+        QueryMsg::Drafts {
+            start_after,
+            limit,
+        } => to_binary(&query_drafts(deps, start_after, limit)?),
+        QueryMsg::Trash {
+            start_after,
+            limit,
+        } => to_binary(&query_trash(deps, start_after, limit)?),
+        QueryMsg::Alias {
+            topic,
+            sub_topic,
+            name,
+        } => to_binary(&query_alias(deps, &topic, &sub_topic, &name)?),
+        QueryMsg::NoteHistory {
+            topic,
+            sub_topic,
+            name,
+            start_after,
+            limit,
+        } => to_binary(&query_note_history(deps, &topic, &sub_topic, &name, start_after, limit)?),
+        QueryMsg::NoteAtRevision {
+            topic,
+            sub_topic,
+            name,
+            revision,
+        } => to_binary(&query_note_at_revision(deps, &topic, &sub_topic, &name, revision)?),
+        QueryMsg::Config {} => to_binary(&CONFIG.load(deps.storage)?),
+        QueryMsg::RemovalStatus {
+            topic,
+            sub_topic,
+        } => to_binary(&query_removal_status(deps, &topic, sub_topic)?),
+        QueryMsg::Upload {
+            topic,
+            sub_topic,
+            name,
+        } => to_binary(&query_upload(deps, &topic, &sub_topic, &name)?),
+        QueryMsg::NoteChunk {
+            topic,
+            sub_topic,
+            name,
+            index,
+            chunk_size,
+        } => {
+            to_binary(&query_note_chunk(deps, &env, &topic, &sub_topic, &name, index, chunk_size)?)
+        },
+        QueryMsg::NoteHash {
+            topic,
+            sub_topic,
+            name,
+        } => to_binary(&query_note_hash(deps, &env, &topic, &sub_topic, &name)?),
+        QueryMsg::NotesByHash {
+            hash,
+            start_after,
+            limit,
+        } => to_binary(&query_notes_by_hash(deps, &env, &hash, start_after, limit)?),
+        QueryMsg::Backlinks {
+            topic,
+            sub_topic,
+            name,
+            start_after,
+            limit,
+        } => to_binary(&query_backlinks(deps, &topic, &sub_topic, &name, start_after, limit)?),
+        QueryMsg::DanglingLinks {
+            topic,
+            sub_topic,
+            name,
+        } => to_binary(&query_dangling_links(deps, &topic, &sub_topic, &name)?),
+        QueryMsg::ListItems {
+            key,
+            start_after,
+            limit,
+            order,
+        } => to_binary(&query_list_items(deps, &env, &key, start_after, limit, order)?),
+        QueryMsg::NoteFields {
+            key,
+            start_after,
+            limit,
+        } => to_binary(&query_note_fields(deps, &env, &key, start_after, limit)?),
+        QueryMsg::MaxNoteSize {
+            topic,
+        } => to_binary(&max_note_size(deps.storage, &topic)?),
+        QueryMsg::NotesByTag {
+            tag,
+            start_after,
+            limit,
+        } => to_binary(&query_notes_by_tag(deps, &env, &tag, start_after, limit)?),
     }
 }
 
 #[entry_point]
-pub fn migrate(mut _deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    Ok(Response::default())
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    migrate_config(deps.storage)?;
+    exec_migrate_storage(deps.storage, &env.block, msg.limit)
 }
 
 #[cfg(test)]
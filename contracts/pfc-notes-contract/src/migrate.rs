@@ -0,0 +1,159 @@
+use cosmwasm_schema::serde::{Deserialize, Serialize};
+use cosmwasm_std::{Addr, BlockInfo, Empty, Order, Response, StdResult, Storage, Timestamp};
+use cw_storage_plus::{Item, Map};
+use pfc_notes::{Compression, Config, NoteEntry, NoteKind, NoteStamp, NoteType, SubTopicKey};
+
+use crate::{
+    error::ContractError,
+    execute::{add_body, sha256_hex},
+    state::{
+        notes, topic_subtopics, NoteMeta, CONFIG, CONFIG_KEY, DEFAULT_HISTORY_DEPTH,
+        DEFAULT_MAX_NOTE_SIZE, DEFAULT_TRASH_RETENTION,
+    },
+};
+
+/// storage written with `"{topic}_{sub_topic}:{name}"` / `"{topic}_{sub_topic}"` string keys
+const LEGACY_SUBTOPICS_KEY: &str = "topic_subtopics_001";
+const LEGACY_SUBTOPICS_INDEX_KEY: &str = "topic_subtopics__subtopics";
+const LEGACY_NOTE_KEY: &str = "topic_subtopics_notes_001";
+const LEGACY_NOTE_INDEX_KEY: &str = "topic_subtopics__note";
+const DEFAULT_MIGRATE_LIMIT: u32 = 100;
+const MAX_MIGRATE_LIMIT: u32 = 500;
+
+/// a note as it may exist in legacy storage, written either before or after notes carried block
+/// stamps.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde")]
+struct StoredNoteEntry {
+    writer: Addr,
+    topic: String,
+    sub_topic: String,
+    name: String,
+    note_type: NoteType,
+    note: String,
+    created_at: Option<NoteStamp>,
+    updated_at: Option<NoteStamp>,
+    revision: Option<u64>,
+    expires_at: Option<Timestamp>,
+}
+
+impl StoredNoteEntry {
+    /// notes written before `created_at`/`updated_at` existed are stamped with `block`
+    fn into_note_entry(self, block: &BlockInfo) -> NoteEntry {
+        let now = NoteStamp::from(block);
+        let hash = sha256_hex(self.note.as_bytes());
+        NoteEntry {
+            writer: self.writer,
+            topic: self.topic,
+            sub_topic: self.sub_topic,
+            name: self.name,
+            note_type: self.note_type,
+            note: self.note,
+            created_at: self.created_at.unwrap_or_else(|| now.clone()),
+            updated_at: self.updated_at.unwrap_or(now),
+            revision: self.revision.unwrap_or(1),
+            expires_at: self.expires_at,
+            tags: vec![],
+            compression: Compression::None,
+            hash,
+            links: vec![],
+            kind: NoteKind::Plain,
+        }
+    }
+}
+
+/// the config as it may exist in storage, from before some of its fields existed
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "cosmwasm_schema::serde")]
+struct StoredConfig {
+    history_depth: Option<u32>,
+    max_note_size: Option<u32>,
+    trash_retention: Option<u64>,
+}
+
+/// fills in defaults for config missing from older contracts
+pub(crate) fn migrate_config(store: &mut dyn Storage) -> StdResult<()> {
+    let stored: Item<StoredConfig> = Item::new(CONFIG_KEY);
+    let stored = stored.may_load(store)?.unwrap_or(StoredConfig {
+        history_depth: None,
+        max_note_size: None,
+        trash_retention: None,
+    });
+    CONFIG.save(
+        store,
+        &Config {
+            history_depth: stored.history_depth.unwrap_or(DEFAULT_HISTORY_DEPTH),
+            max_note_size: stored.max_note_size.unwrap_or(DEFAULT_MAX_NOTE_SIZE),
+            trash_retention: stored.trash_retention.unwrap_or(DEFAULT_TRASH_RETENTION),
+        },
+    )
+}
+
+/// moves up to `limit` notes and subtopics from the string keyed maps to the tuple keyed ones,
+/// removing them from the legacy maps. the legacy indexes are cleared from what is left of the
+/// batch. returns the number of notes moved, and whether nothing legacy is left
+pub(crate) fn migrate_storage_keys(
+    store: &mut dyn Storage,
+    block: &BlockInfo,
+    limit: Option<u32>,
+) -> StdResult<(u32, bool)> {
+    let mut budget = limit.unwrap_or(DEFAULT_MIGRATE_LIMIT).clamp(1, MAX_MIGRATE_LIMIT) as usize;
+    let legacy_subtopics: Map<String, SubTopicKey> = Map::new(LEGACY_SUBTOPICS_KEY);
+    let subtopics = legacy_subtopics
+        .range(store, None, None, Order::Ascending)
+        .take(budget)
+        .collect::<StdResult<Vec<(String, SubTopicKey)>>>()?;
+    budget -= subtopics.len();
+    for (key, subtopic) in subtopics {
+        topic_subtopics().save(store, (&subtopic.topic, &subtopic.sub_topic), &subtopic)?;
+        legacy_subtopics.remove(store, key);
+    }
+
+    let legacy_notes: Map<String, StoredNoteEntry> = Map::new(LEGACY_NOTE_KEY);
+    let entries = legacy_notes
+        .range(store, None, None, Order::Ascending)
+        .take(budget)
+        .map(|item| item.map(|(key, v)| (key, v.into_note_entry(block))))
+        .collect::<StdResult<Vec<(String, NoteEntry)>>>()?;
+    budget -= entries.len();
+    let count = entries.len() as u32;
+    for (key, entry) in entries {
+        add_body(store, &entry.hash, &entry.note)?;
+        notes().save(
+            store,
+            (&entry.topic, &entry.sub_topic, &entry.name),
+            &NoteMeta::from(entry.clone()),
+        )?;
+        legacy_notes.remove(store, key);
+    }
+
+    for namespace in [LEGACY_SUBTOPICS_INDEX_KEY, LEGACY_NOTE_INDEX_KEY] {
+        budget -= clear_namespace(store, namespace, budget);
+    }
+    // every step stopped short of the budget, so found nothing more to do
+    Ok((count, budget > 0))
+}
+
+/// a batch of `migrate_storage_keys`, run by the migration and by `MigrateStorage`
+pub(crate) fn exec_migrate_storage(
+    store: &mut dyn Storage,
+    block: &BlockInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let (migrated, done) = migrate_storage_keys(store, block, limit)?;
+    Ok(Response::default()
+        .add_attribute("migrated_notes", migrated.to_string())
+        .add_attribute("done", done.to_string()))
+}
+
+/// removes up to `limit` keys stored under `namespace`, whatever its layout, returning how many
+/// were removed
+fn clear_namespace(store: &mut dyn Storage, namespace: &'static str, limit: usize) -> usize {
+    let raw: Map<&[u8], Empty> = Map::new(namespace);
+    let keys =
+        raw.keys_raw(store, None, None, Order::Ascending).take(limit).collect::<Vec<Vec<u8>>>();
+    for key in &keys {
+        raw.remove(store, key);
+    }
+    keys.len()
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use crate::{error::ContractError, json::Json};
+
+/// an RFC 6902 operation
+enum Operation {
+    Add {
+        path: String,
+        value: Json,
+    },
+    Remove {
+        path: String,
+    },
+    Replace {
+        path: String,
+        value: Json,
+    },
+    Move {
+        from: String,
+        path: String,
+    },
+    Copy {
+        from: String,
+        path: String,
+    },
+    Test {
+        path: String,
+        value: Json,
+    },
+}
+
+impl Operation {
+    fn from_json(operation: Json) -> Result<Operation, ContractError> {
+        let Json::Object(mut fields) = operation else {
+            return Err(patch_err("an operation must be an object".to_string()));
+        };
+        let op = string_field(&mut fields, "op")?;
+        Ok(match op.as_str() {
+            "add" => Operation::Add {
+                path: string_field(&mut fields, "path")?,
+                value: field(&mut fields, "value")?,
+            },
+            "remove" => Operation::Remove {
+                path: string_field(&mut fields, "path")?,
+            },
+            "replace" => Operation::Replace {
+                path: string_field(&mut fields, "path")?,
+                value: field(&mut fields, "value")?,
+            },
+            "move" => Operation::Move {
+                from: string_field(&mut fields, "from")?,
+                path: string_field(&mut fields, "path")?,
+            },
+            "copy" => Operation::Copy {
+                from: string_field(&mut fields, "from")?,
+                path: string_field(&mut fields, "path")?,
+            },
+            "test" => Operation::Test {
+                path: string_field(&mut fields, "path")?,
+                value: field(&mut fields, "value")?,
+            },
+            _ => return Err(patch_err(format!("unknown op {:?}", op))),
+        })
+    }
+}
+
+fn field(fields: &mut BTreeMap<String, Json>, name: &str) -> Result<Json, ContractError> {
+    fields.remove(name).ok_or_else(|| patch_err(format!("missing field `{}`", name)))
+}
+
+fn string_field(fields: &mut BTreeMap<String, Json>, name: &str) -> Result<String, ContractError> {
+    match field(fields, name)? {
+        Json::String(value) => Ok(value),
+        _ => Err(patch_err(format!("`{}` must be a string", name))),
+    }
+}
+
+/// applies an RFC 7396 merge patch
+pub fn merge_patch(target: &mut Json, patch: &Json) {
+    let Json::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !matches!(target, Json::Object(_)) {
+        *target = Json::Object(Default::default());
+    }
+    if let Json::Object(target) = target {
+        for (key, value) in patch {
+            if *value == Json::Null {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Json::Null), value);
+            }
+        }
+    }
+}
+
+/// applies an RFC 6902 patch, a JSON array of operations. nothing is applied if any operation
+/// fails, or if the document written out would at any point be longer than `limit` bytes, as
+/// repeated copies could otherwise double it with every operation
+pub fn json_patch(target: &mut Json, patch: &str, limit: usize) -> Result<(), ContractError> {
+    let Json::Array(operations) = Json::parse(patch).map_err(ContractError::InvalidPatch)? else {
+        return Err(patch_err("a patch must be an array of operations".to_string()));
+    };
+    let operations =
+        operations.into_iter().map(Operation::from_json).collect::<Result<Vec<_>, _>>()?;
+    let mut doc = target.clone();
+    let mut size = encoded_len(&doc);
+    for operation in operations {
+        match operation {
+            Operation::Add {
+                path,
+                value,
+            } => add(&mut doc, &mut size, &path, value)?,
+            Operation::Remove {
+                path,
+            } => {
+                remove(&mut doc, &mut size, &path)?;
+            },
+            Operation::Replace {
+                path,
+                value,
+            } => {
+                remove(&mut doc, &mut size, &path)?;
+                add(&mut doc, &mut size, &path, value)?;
+            },
+            Operation::Move {
+                from,
+                path,
+            } => {
+                if path.starts_with(&format!("{}/", from)) {
+                    return Err(patch_err(format!("cannot move {} into itself", from)));
+                }
+                let value = remove(&mut doc, &mut size, &from)?;
+                add(&mut doc, &mut size, &path, value)?;
+            },
+            Operation::Copy {
+                from,
+                path,
+            } => {
+                let value = get(&doc, &from)?.clone();
+                add(&mut doc, &mut size, &path, value)?;
+            },
+            Operation::Test {
+                path,
+                value,
+            } => {
+                if *get(&doc, &path)? != value {
+                    return Err(patch_err(format!("test failed at {}", path)));
+                }
+            },
+        }
+        if size > limit {
+            return Err(ContractError::NoteTooLarge {
+                size,
+                limit: limit as u32,
+            });
+        }
+    }
+    *target = doc;
+    Ok(())
+}
+
+fn patch_err(msg: String) -> ContractError {
+    ContractError::InvalidPatch(msg)
+}
+
+/// the length of a value written out, as `Json` writes it
+fn encoded_len(value: &Json) -> usize {
+    struct Counter(usize);
+    impl std::fmt::Write for Counter {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    // writing to a counter does not fail
+    let _ = std::fmt::write(&mut counter, format_args!("{}", value));
+    counter.0
+}
+
+/// the length of an object member, `"key":value`
+fn member_len(key: &str, value: &Json) -> usize {
+    encoded_len(&Json::String(key.to_string())) + 1 + encoded_len(value)
+}
+
+/// splits an RFC 6901 JSON pointer into its unescaped tokens
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, ContractError> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(patch_err(format!("invalid pointer {:?}", pointer)));
+    };
+    Ok(rest.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+/// an array index, which may not have leading zeros
+fn parse_index(token: &str, len: usize) -> Result<usize, ContractError> {
+    let valid = !token.is_empty()
+        && token.chars().all(|c| c.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(index) if valid && index < len => Ok(index),
+        _ => Err(patch_err(format!("invalid array index {:?}", token))),
+    }
+}
+
+fn get<'a>(doc: &'a Json, pointer: &str) -> Result<&'a Json, ContractError> {
+    let mut value = doc;
+    for token in parse_pointer(pointer)? {
+        value = match value {
+            Json::Object(map) => map.get(&token),
+            Json::Array(items) => items.get(parse_index(&token, items.len())?),
+            _ => None,
+        }
+        .ok_or_else(|| patch_err(format!("{} does not exist", pointer)))?;
+    }
+    Ok(value)
+}
+
+/// the container holding the last token of `pointer`, with that token
+fn parent_mut<'a>(
+    doc: &'a mut Json,
+    pointer: &str,
+) -> Result<(&'a mut Json, Option<String>), ContractError> {
+    let mut tokens = parse_pointer(pointer)?;
+    let last = tokens.pop();
+    let mut value = doc;
+    for token in tokens {
+        value = match value {
+            Json::Object(map) => map.get_mut(&token),
+            Json::Array(items) => {
+                let index = parse_index(&token, items.len())?;
+                items.get_mut(index)
+            },
+            _ => None,
+        }
+        .ok_or_else(|| patch_err(format!("{} does not exist", pointer)))?;
+    }
+    Ok((value, last))
+}
+
+/// adds `new` at `pointer`, keeping `size`, the length of the document written out, up to date
+fn add(doc: &mut Json, size: &mut usize, pointer: &str, new: Json) -> Result<(), ContractError> {
+    let (parent, token) = parent_mut(doc, pointer)?;
+    let Some(token) = token else {
+        *size = encoded_len(&new);
+        *parent = new;
+        return Ok(());
+    };
+    match parent {
+        Json::Object(map) => {
+            let added = member_len(&token, &new);
+            match map.insert(token.clone(), new) {
+                Some(old) => *size = *size + added - member_len(&token, &old),
+                // a comma before every member but the first
+                None => *size += added + (map.len() > 1) as usize,
+            }
+        },
+        Json::Array(items) => {
+            let index = match token.as_str() {
+                "-" => items.len(),
+                // an index one past the end appends
+                token => parse_index(token, items.len() + 1)?,
+            };
+            *size += encoded_len(&new) + !items.is_empty() as usize;
+            items.insert(index, new);
+        },
+        _ => return Err(patch_err(format!("{} does not exist", pointer))),
+    }
+    Ok(())
+}
+
+/// removes the value at `pointer`, keeping `size` up to date as `add` does
+fn remove(doc: &mut Json, size: &mut usize, pointer: &str) -> Result<Json, ContractError> {
+    let (parent, token) = parent_mut(doc, pointer)?;
+    let Some(token) = token else {
+        *size = encoded_len(&Json::Null);
+        return Ok(std::mem::replace(parent, Json::Null));
+    };
+    let removed = match parent {
+        Json::Object(map) => map.remove(&token).map(|old| {
+            *size -= member_len(&token, &old) + !map.is_empty() as usize;
+            old
+        }),
+        Json::Array(items) => {
+            let index = parse_index(&token, items.len())?;
+            let old = items.remove(index);
+            *size -= encoded_len(&old) + !items.is_empty() as usize;
+            Some(old)
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| patch_err(format!("{} does not exist", pointer)))
+}
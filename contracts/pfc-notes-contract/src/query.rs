@@ -1,11 +1,26 @@
-use cosmwasm_std::{Deps, Order, StdResult};
-use cw_storage_plus::Bound;
-use pfc_notes::{NoteEntry, NoteKey, NoteResponse, NoteWriter, SubTopicKey};
+use cosmwasm_std::{Deps, Env, Order, StdError, StdResult};
+use cw_storage_plus::{Bound, KeyDeserialize, PrimaryKey};
+use pfc_notes::{
+    Backlinks, ListItem, ListOrder, NoteAlias, NoteChunk, NoteEntry, NoteField, NoteKey, NoteKind,
+    NotePage, NoteResponse, NoteStatus, NoteWriter, RemovalStatus, Subtopic, Topic, TrashedNote,
+    UploadStatus,
+};
 
-use crate::state::{notes, topic_subtopics, TOPICSMAP, WRITER};
+use crate::state::{
+    aliases, notes, resolve_alias, topic_subtopics, trash, NoteMeta, NotePk, DRAFTS, LIST_ITEMS,
+    MAP_FIELDS, NOTE_BODIES, NOTE_HISTORY, SUBTOPIC_INFO, TOPICSMAP, TOPIC_INFO, UPLOADS,
+    UPLOAD_CHUNKS, WRITER,
+};
 
 const DEFAULT_LIMIT: u32 = 10;
 const MAX_LIMIT: u32 = 30;
+const DEFAULT_CHUNK_SIZE: u32 = 8 * 1024;
+// at least 4 bytes, so every chunk holds a whole character
+const MIN_CHUNK_SIZE: u32 = 64;
+const MAX_CHUNK_SIZE: u32 = 32 * 1024;
+const MAX_REMOVAL_COUNT: usize = 100;
+/// the most notes a page of live notes looks at, expired ones included
+const MAX_SCAN: usize = 100;
 
 pub(crate) fn query_writer(
     deps: Deps,
@@ -38,16 +53,22 @@ pub(crate) fn query_topics(
     deps: Deps,
     start_after: Option<String>,
     limit: Option<u32>,
-) -> StdResult<NoteResponse<String>> {
+) -> StdResult<NoteResponse<Topic>> {
     let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
     let start = start_after.map(Bound::exclusive);
 
     Ok(NoteResponse {
         entries: TOPICSMAP
-            .range(deps.storage, start, None, Order::Ascending)
+            .keys(deps.storage, start, None, Order::Ascending)
             .take(limit_amt)
-            .map(|item| item.map(|(k, _)| k))
-            .collect::<StdResult<Vec<String>>>()?,
+            .map(|item| {
+                let topic = item?;
+                Ok(Topic {
+                    info: TOPIC_INFO.may_load(deps.storage, &topic)?.unwrap_or_default(),
+                    topic,
+                })
+            })
+            .collect::<StdResult<Vec<Topic>>>()?,
     })
 }
 
@@ -56,17 +77,9 @@ pub(crate) fn query_sub_topics(
     topic: &str,
     start_after: Option<String>,
     limit: Option<u32>,
-) -> StdResult<NoteResponse<SubTopicKey>> {
+) -> StdResult<NoteResponse<Subtopic>> {
     let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = if let Some(start_sub) = start_after {
-        let subtopic_key = SubTopicKey {
-            topic: topic.to_string(),
-            sub_topic: start_sub,
-        };
-        Some(Bound::exclusive(subtopic_key.to_string()))
-    } else {
-        None
-    };
+    let start = start_after.as_deref().map(|start_sub| Bound::exclusive((topic, start_sub)));
 
     Ok(NoteResponse {
         entries: topic_subtopics()
@@ -75,52 +88,551 @@ pub(crate) fn query_sub_topics(
             .prefix(topic.into())
             .range(deps.storage, start, None, Order::Ascending)
             .take(limit_amt)
-            .map(|item| item.map(|(_, v)| v))
-            .collect::<StdResult<Vec<SubTopicKey>>>()?,
+            .map(|item| {
+                let (_, key) = item?;
+                Ok(Subtopic {
+                    info: SUBTOPIC_INFO
+                        .may_load(deps.storage, (&key.topic, &key.sub_topic))?
+                        .unwrap_or_default(),
+                    topic: key.topic,
+                    sub_topic: key.sub_topic,
+                })
+            })
+            .collect::<StdResult<Vec<Subtopic>>>()?,
     })
 }
 
 pub(crate) fn query_entries(
     deps: Deps,
+    env: &Env,
     topic: &str,
     sub_topic: &str,
     start_after: Option<String>,
     limit: Option<u32>,
-) -> StdResult<NoteResponse<NoteEntry>> {
+) -> StdResult<NotePage<String>> {
     let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    //  let start = start_after.map(Bound::exclusive);
-    let start = if let Some(start_note) = start_after {
+    let start =
+        start_after.as_deref().map(|start_note| Bound::exclusive((topic, sub_topic, start_note)));
+    let items = notes()
+        .idx
+        .subtopic
+        .prefix((topic.to_string(), sub_topic.to_string()))
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| item.map(|((_, _, name), v)| (name, v)));
+    live_page(deps, env, items, limit_amt)
+}
+
+fn is_expired(env: &Env, note: &NoteMeta) -> bool {
+    note.expires_at.map(|t| t <= env.block.time).unwrap_or(false)
+}
+
+/// collects up to `limit` live notes, looking at no more than `MAX_SCAN`. the key of the last
+/// note looked at is kept for the next page while there may be more
+fn live_page<K>(
+    deps: Deps,
+    env: &Env,
+    items: impl Iterator<Item = StdResult<(K, NoteMeta)>>,
+    limit: usize,
+) -> StdResult<NotePage<K>> {
+    let mut items = items.peekable();
+    let mut entries = vec![];
+    let mut last = None;
+    for _ in 0..MAX_SCAN {
+        if entries.len() == limit {
+            break;
+        }
+        let Some(item) = items.next() else {
+            break;
+        };
+        let (key, meta) = item?;
+        if !is_expired(env, &meta) {
+            entries.push(with_body(deps, meta)?);
+        }
+        last = Some(key);
+    }
+    Ok(NotePage {
+        entries,
+        start_after: if items.peek().is_some() {
+            last
+        } else {
+            None
+        },
+    })
+}
+
+/// joins a note with its body
+fn with_body(deps: Deps, meta: NoteMeta) -> StdResult<NoteEntry> {
+    let body = NOTE_BODIES.load(deps.storage, &meta.hash)?;
+    Ok(meta.into_note_entry(body.note))
+}
+
+/// aliases are followed to the note they name
+pub(crate) fn query_note(
+    deps: Deps,
+    env: &Env,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> StdResult<NoteStatus> {
+    let key = resolve_alias(
+        deps.storage,
+        NoteKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+            name: name.to_string(),
+        },
+    )?;
+    let meta = notes().load(deps.storage, (&key.topic, &key.sub_topic, &key.name))?;
+    match meta.expires_at {
+        Some(expired_at) if is_expired(env, &meta) => Ok(NoteStatus::Expired {
+            key,
+            expired_at,
+        }),
+        _ => Ok(NoteStatus::Live(Box::new(with_body(deps, meta)?))),
+    }
+}
+
+pub(crate) fn query_alias(
+    deps: Deps,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> StdResult<NoteAlias> {
+    aliases().load(deps.storage, (topic, sub_topic, name))
+}
+
+/// the note, without loading its body
+fn query_note_meta(
+    deps: Deps,
+    env: &Env,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> StdResult<NoteMeta> {
+    let note = notes().load(deps.storage, (topic, sub_topic, name))?;
+    if is_expired(env, &note) {
         let note_key = NoteKey {
             topic: topic.to_string(),
             sub_topic: sub_topic.to_string(),
-            name: start_note,
+            name: name.to_string(),
         };
-        Some(Bound::exclusive(note_key.to_string()))
-    } else {
-        None
-    };
+        return Err(StdError::generic_err(format!("Note has expired: {}", note_key)));
+    }
+    Ok(note)
+}
+
+/// fails for a note in the trash, whose history stays under its key until it is purged
+fn assert_not_trashed(deps: Deps, topic: &str, sub_topic: &str, name: &str) -> StdResult<()> {
+    if trash().has(deps.storage, (topic, sub_topic, name)) {
+        let note_key = NoteKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+            name: name.to_string(),
+        };
+        return Err(StdError::generic_err(format!("Note is in the trash: {}", note_key)));
+    }
+    Ok(())
+}
+
+pub(crate) fn query_note_history(
+    deps: Deps,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<NoteResponse<NoteEntry>> {
+    assert_not_trashed(deps, topic, sub_topic, name)?;
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
     Ok(NoteResponse {
-        entries: notes()
-            .idx
-            .subtopic
-            .prefix(format!("{}_{}", topic, sub_topic))
+        entries: NOTE_HISTORY
+            .prefix((topic, sub_topic, name))
             .range(deps.storage, start, None, Order::Ascending)
             .take(limit_amt)
-            .map(|item| item.map(|(_k, v)| v))
+            .map(|item| item.and_then(|(_, v)| with_body(deps, v)))
             .collect::<StdResult<Vec<NoteEntry>>>()?,
     })
 }
 
-pub(crate) fn query_note(
+pub(crate) fn query_note_at_revision(
     deps: Deps,
     topic: &str,
     sub_topic: &str,
     name: &str,
+    revision: u64,
 ) -> StdResult<NoteEntry> {
-    let note_key = NoteKey {
-        topic: topic.to_string(),
-        sub_topic: sub_topic.to_string(),
-        name: name.to_string(),
+    assert_not_trashed(deps, topic, sub_topic, name)?;
+    let pk = (topic, sub_topic, name);
+    let meta = match notes().may_load(deps.storage, pk)? {
+        Some(note) if note.revision == revision => note,
+        _ => NOTE_HISTORY.load(deps.storage, (pk, revision))?,
+    };
+    with_body(deps, meta)
+}
+
+/// counts a removal in progress, stopping at `MAX_REMOVAL_COUNT` so the query stays bounded
+fn count_remaining<T>(keys: impl Iterator<Item = T>) -> u64 {
+    keys.take(MAX_REMOVAL_COUNT).count() as u64
+}
+
+pub(crate) fn query_removal_status(
+    deps: Deps,
+    topic: &str,
+    sub_topic: Option<String>,
+) -> StdResult<RemovalStatus> {
+    Ok(match sub_topic {
+        Some(sub_topic) => RemovalStatus {
+            sub_topics: topic_subtopics().has(deps.storage, (topic, &sub_topic)) as u64,
+            notes: count_remaining(notes().prefix((topic, &sub_topic)).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            aliases: count_remaining(aliases().prefix((topic, &sub_topic)).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            trashed: count_remaining(trash().prefix((topic, &sub_topic)).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            drafts: count_remaining(DRAFTS.prefix((topic, &sub_topic)).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            uploads: count_remaining(UPLOADS.prefix((topic, &sub_topic)).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+        },
+        None => RemovalStatus {
+            sub_topics: count_remaining(topic_subtopics().prefix(topic).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            notes: count_remaining(notes().sub_prefix(topic).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            aliases: count_remaining(aliases().sub_prefix(topic).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            trashed: count_remaining(trash().sub_prefix(topic).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            drafts: count_remaining(DRAFTS.sub_prefix(topic).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+            uploads: count_remaining(UPLOADS.sub_prefix(topic).keys_raw(
+                deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            )),
+        },
+    })
+}
+
+pub(crate) fn query_notes_by_tag(
+    deps: Deps,
+    env: &Env,
+    tag: &str,
+    start_after: Option<NoteKey>,
+    limit: Option<u32>,
+) -> StdResult<NotePage<NoteKey>> {
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_pk = start_after
+        .map(|key| (key.topic.as_str(), key.sub_topic.as_str(), key.name.as_str()).joined_key());
+    let start = start_pk.as_deref().map(Bound::exclusive);
+
+    let idx = notes().idx.tag.idx;
+    let items = idx.prefix(tag).keys(deps.storage, start, None, Order::Ascending).map(|pk| {
+        let (topic, sub_topic, name) = NotePk::from_vec(pk?)?;
+        let meta = notes().load(deps.storage, (&topic, &sub_topic, &name))?;
+        Ok((
+            NoteKey {
+                topic,
+                sub_topic,
+                name,
+            },
+            meta,
+        ))
+    });
+    live_page(deps, env, items, limit_amt)
+}
+
+pub(crate) fn query_upload(
+    deps: Deps,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> StdResult<UploadStatus> {
+    let upload = UPLOADS.load(deps.storage, (topic, sub_topic, name))?;
+    let received = UPLOAD_CHUNKS
+        .prefix((topic, sub_topic, name))
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<u32>>>()?;
+    Ok(UploadStatus {
+        upload,
+        received,
+    })
+}
+
+/// chunk boundaries are multiples of `chunk_size`, moved back to the nearest character boundary
+pub(crate) fn query_note_chunk(
+    deps: Deps,
+    env: &Env,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    index: u32,
+    chunk_size: Option<u32>,
+) -> StdResult<NoteChunk> {
+    let note = match query_note(deps, env, topic, sub_topic, name)? {
+        NoteStatus::Live(entry) => entry.note,
+        NoteStatus::Expired {
+            key,
+            ..
+        } => return Err(StdError::generic_err(format!("Note has expired: {}", key))),
+    };
+    let chunk_size =
+        chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE) as usize;
+    let total_chunks = ((note.len() + chunk_size - 1) / chunk_size).max(1) as u32;
+    if index >= total_chunks {
+        return Err(StdError::generic_err(format!(
+            "Chunk {} is out of range, the note has {} chunks",
+            index, total_chunks
+        )));
+    }
+    let boundary = |i: usize| {
+        let mut pos = (i * chunk_size).min(note.len());
+        while !note.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        pos
     };
-    notes().load(deps.storage, note_key.to_string())
+    let index_amt = index as usize;
+    Ok(NoteChunk {
+        index,
+        total_chunks,
+        data: note[boundary(index_amt)..boundary(index_amt + 1)].to_string(),
+    })
+}
+
+pub(crate) fn query_note_hash(
+    deps: Deps,
+    env: &Env,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> StdResult<String> {
+    Ok(query_note_meta(deps, env, topic, sub_topic, name)?.hash)
+}
+
+pub(crate) fn query_notes_by_hash(
+    deps: Deps,
+    env: &Env,
+    hash: &str,
+    start_after: Option<NoteKey>,
+    limit: Option<u32>,
+) -> StdResult<NotePage<NoteKey>> {
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(|key| {
+        Bound::exclusive((key.topic.as_str(), key.sub_topic.as_str(), key.name.as_str()))
+    });
+
+    let items = notes()
+        .idx
+        .hash
+        .prefix(hash.to_ascii_lowercase())
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| {
+            item.map(|((topic, sub_topic, name), v)| {
+                (
+                    NoteKey {
+                        topic,
+                        sub_topic,
+                        name,
+                    },
+                    v,
+                )
+            })
+        });
+    live_page(deps, env, items, limit_amt)
+}
+
+pub(crate) fn query_backlinks(
+    deps: Deps,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+    start_after: Option<NoteKey>,
+    limit: Option<u32>,
+) -> StdResult<Backlinks> {
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_pk = start_after
+        .map(|key| (key.topic.as_str(), key.sub_topic.as_str(), key.name.as_str()).joined_key());
+    let start = start_pk.as_deref().map(Bound::exclusive);
+
+    let entries = notes()
+        .idx
+        .link
+        .idx
+        .prefix((topic, sub_topic, name))
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit_amt)
+        .map(|pk| {
+            let (topic, sub_topic, name) = NotePk::from_vec(pk?)?;
+            Ok(NoteKey {
+                topic,
+                sub_topic,
+                name,
+            })
+        })
+        .collect::<StdResult<Vec<NoteKey>>>()?;
+    Ok(Backlinks {
+        exists: link_resolves(deps, NoteKey::from((topic, sub_topic, name))),
+        entries,
+    })
+}
+
+/// whether a link reaches a note, directly or through aliases. a broken alias chain counts as
+/// not reaching one
+fn link_resolves(deps: Deps, key: NoteKey) -> bool {
+    resolve_alias(deps.storage, key)
+        .map_or(false, |key| notes().has(deps.storage, (&key.topic, &key.sub_topic, &key.name)))
+}
+
+pub(crate) fn query_dangling_links(
+    deps: Deps,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> StdResult<Vec<NoteKey>> {
+    let note = notes().load(deps.storage, (topic, sub_topic, name))?;
+    Ok(note.links.into_iter().filter(|link| !link_resolves(deps, link.clone())).collect())
+}
+
+pub(crate) fn query_list_items(
+    deps: Deps,
+    env: &Env,
+    key: &NoteKey,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order: Option<ListOrder>,
+) -> StdResult<NoteResponse<ListItem>> {
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let meta = query_note_meta(deps, env, &key.topic, &key.sub_topic, &key.name)?;
+    if !matches!(meta.kind, NoteKind::List { .. }) {
+        return Err(StdError::generic_err(format!("Note {} is not a list note", key)));
+    }
+    let start = start_after.map(Bound::exclusive);
+    let (min, max, order) = match order.unwrap_or_default() {
+        ListOrder::Ascending => (start, None, Order::Ascending),
+        ListOrder::Descending => (None, start, Order::Descending),
+    };
+
+    Ok(NoteResponse {
+        entries: LIST_ITEMS
+            .prefix((&key.topic, &key.sub_topic, &key.name))
+            .range(deps.storage, min, max, order)
+            .take(limit_amt)
+            .map(|item| item.map(|(_, v)| v))
+            .collect::<StdResult<Vec<ListItem>>>()?,
+    })
+}
+
+pub(crate) fn query_note_fields(
+    deps: Deps,
+    env: &Env,
+    key: &NoteKey,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<NoteResponse<NoteField>> {
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let meta = query_note_meta(deps, env, &key.topic, &key.sub_topic, &key.name)?;
+    if !matches!(meta.kind, NoteKind::Map { .. }) {
+        return Err(StdError::generic_err(format!("Note {} is not a map note", key)));
+    }
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    Ok(NoteResponse {
+        entries: MAP_FIELDS
+            .prefix((&key.topic, &key.sub_topic, &key.name))
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit_amt)
+            .map(|item| item.map(|(_, v)| v))
+            .collect::<StdResult<Vec<NoteField>>>()?,
+    })
+}
+
+pub(crate) fn query_trash(
+    deps: Deps,
+    start_after: Option<NoteKey>,
+    limit: Option<u32>,
+) -> StdResult<NoteResponse<TrashedNote>> {
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(|key| {
+        Bound::exclusive((key.topic.as_str(), key.sub_topic.as_str(), key.name.as_str()))
+    });
+
+    trash()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit_amt)
+        .map(|item| {
+            let (_, entry) = item?;
+            Ok(TrashedNote {
+                note: with_body(deps, entry.meta)?,
+                removed_by: entry.removed_by,
+                removed_at: entry.removed_at,
+                purge_after: entry.purge_after,
+            })
+        })
+        .collect::<StdResult<Vec<TrashedNote>>>()
+        .map(|entries| NoteResponse {
+            entries,
+        })
+}
+
+pub(crate) fn query_drafts(
+    deps: Deps,
+    start_after: Option<NoteKey>,
+    limit: Option<u32>,
+) -> StdResult<NoteResponse<NoteEntry>> {
+    let limit_amt = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(|key| {
+        Bound::exclusive((key.topic.as_str(), key.sub_topic.as_str(), key.name.as_str()))
+    });
+
+    Ok(NoteResponse {
+        entries: DRAFTS
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit_amt)
+            .map(|item| item.and_then(|(_, v)| with_body(deps, v)))
+            .collect::<StdResult<Vec<NoteEntry>>>()?,
+    })
 }
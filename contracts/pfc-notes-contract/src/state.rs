@@ -1,22 +1,64 @@
-use cosmwasm_std::Addr;
-use cw_storage_plus::{Index, IndexList, IndexedMap, Map, MultiIndex};
-use pfc_notes::{NoteEntry, SubTopicKey};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Empty, StdError, StdResult, Storage, Timestamp};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use pfc_notes::{
+    Compression, Config, ListItem, NoteAlias, NoteEntry, NoteField, NoteKey, NoteKind, NoteStamp,
+    NoteType, PendingUpload, SubTopicKey, TopicInfo,
+};
 
 pub(crate) const TOPICSET_KEY: &str = "topic_001";
 pub(crate) const WRITERS_KEY: &str = "writers_001";
-pub(crate) const SUBTOPICS_KEY: &str = "topic_subtopics_001";
-pub(crate) const SUBTOPICS_INDEX_KEY: &str = "topic_subtopics__subtopics";
-pub(crate) const NOTE_KEY: &str = "topic_subtopics_notes_001";
-pub(crate) const NOTE_INDEX_KEY: &str = "topic_subtopics__note";
+pub(crate) const SUBTOPICS_KEY: &str = "topic_subtopics_002";
+pub(crate) const SUBTOPICS_INDEX_KEY: &str = "topic_subtopics__subtopics_002";
+pub(crate) const NOTE_KEY: &str = "topic_subtopics_notes_002";
+pub(crate) const NOTE_INDEX_KEY: &str = "topic_subtopics__note_002";
+pub(crate) const NOTE_EXPIRY_INDEX_KEY: &str = "topic_subtopics__note_expiry_002";
+pub(crate) const NOTE_TAG_INDEX_KEY: &str = "topic_subtopics__note_tag_002";
+pub(crate) const NOTE_HASH_INDEX_KEY: &str = "topic_subtopics__note_hash_002";
+pub(crate) const NOTE_LINK_INDEX_KEY: &str = "topic_subtopics__note_link_002";
+pub(crate) const NOTE_HISTORY_KEY: &str = "note_history_002";
+pub(crate) const NOTE_BODIES_KEY: &str = "note_bodies_001";
+pub(crate) const CONFIG_KEY: &str = "config_001";
+pub(crate) const TOPIC_NOTE_SIZE_KEY: &str = "topic_note_size_001";
+pub(crate) const TOPIC_INFO_KEY: &str = "topic_info_001";
+pub(crate) const SUBTOPIC_INFO_KEY: &str = "subtopic_info_001";
+pub(crate) const LISTS_KEY: &str = "note_lists_001";
+pub(crate) const LIST_ITEMS_KEY: &str = "note_list_items_001";
+pub(crate) const MAP_FIELDS_KEY: &str = "note_map_fields_001";
+pub(crate) const ALIASES_KEY: &str = "note_aliases_001";
+pub(crate) const ALIAS_TARGET_INDEX_KEY: &str = "note_aliases__target_001";
+pub(crate) const TRASH_KEY: &str = "note_trash_001";
+pub(crate) const TRASH_PURGE_INDEX_KEY: &str = "note_trash__purge_001";
+pub(crate) const DRAFTS_KEY: &str = "note_drafts_001";
+pub(crate) const UPLOADS_KEY: &str = "uploads_001";
+pub(crate) const UPLOAD_CHUNKS_KEY: &str = "upload_chunks_001";
 
-//pub(crate) const NOTES_KEY: &str = "notes_001";
+pub(crate) const DEFAULT_HISTORY_DEPTH: u32 = 5;
+pub(crate) const DEFAULT_MAX_NOTE_SIZE: u32 = 32 * 1024;
+pub(crate) const DEFAULT_TRASH_RETENTION: u64 = 30 * 24 * 60 * 60;
+pub(crate) const MAX_HISTORY_DEPTH: u32 = 50;
+pub(crate) const MAX_UPLOAD_CHUNKS: u32 = 256;
+/// the most items a list holds, whatever its capacity
+pub(crate) const MAX_LIST_LEN: u32 = 1000;
+/// the most bytes a list holds across its items
+pub(crate) const MAX_LIST_SIZE: u32 = 1024 * 1024;
+pub(crate) const MAX_MAP_FIELDS: u32 = 1000;
+/// the most bytes a map holds across its field names and values
+pub(crate) const MAX_MAP_SIZE: u32 = 1024 * 1024;
+/// the most aliases followed to reach a note
+pub(crate) const MAX_ALIAS_DEPTH: usize = 8;
+
+/// (topic, sub_topic)
+pub type SubTopicPk<'a> = (&'a str, &'a str);
+/// (topic, sub_topic, name)
+pub type NotePk<'a> = (&'a str, &'a str, &'a str);
 
 pub fn topic_subtopic_idx(_pk: &[u8], d: &SubTopicKey) -> String {
     d.topic.clone()
 }
 
 pub struct TopicSubtopicIndexes<'a> {
-    pub topic: MultiIndex<'a, String, SubTopicKey, String>,
+    pub topic: MultiIndex<'a, String, SubTopicKey, SubTopicPk<'a>>,
 }
 
 impl<'a> IndexList<SubTopicKey> for TopicSubtopicIndexes<'a> {
@@ -26,7 +68,8 @@ impl<'a> IndexList<SubTopicKey> for TopicSubtopicIndexes<'a> {
     }
 }
 
-pub fn topic_subtopics<'a>() -> IndexedMap<'a, String, SubTopicKey, TopicSubtopicIndexes<'a>> {
+pub fn topic_subtopics<'a>() -> IndexedMap<'a, SubTopicPk<'a>, SubTopicKey, TopicSubtopicIndexes<'a>>
+{
     IndexedMap::new(
         SUBTOPICS_KEY,
         TopicSubtopicIndexes {
@@ -35,32 +78,272 @@ pub fn topic_subtopics<'a>() -> IndexedMap<'a, String, SubTopicKey, TopicSubtopi
     )
 }
 
-pub fn note_idx(_pk: &[u8], d: &NoteEntry) -> String {
-    format!("{}_{}", d.topic, d.sub_topic)
+/// a `NoteEntry` without its body, which is kept in `NOTE_BODIES` under `hash`
+#[cw_serde]
+pub struct NoteMeta {
+    pub writer: Addr,
+    pub topic: String,
+    pub sub_topic: String,
+    pub name: String,
+    pub note_type: NoteType,
+    pub created_at: NoteStamp,
+    pub updated_at: NoteStamp,
+    pub revision: u64,
+    pub expires_at: Option<Timestamp>,
+    pub tags: Vec<String>,
+    pub compression: Compression,
+    pub hash: String,
+    pub links: Vec<NoteKey>,
+    pub kind: NoteKind,
+}
+
+impl NoteMeta {
+    pub fn into_note_entry(self, note: String) -> NoteEntry {
+        NoteEntry {
+            writer: self.writer,
+            topic: self.topic,
+            sub_topic: self.sub_topic,
+            name: self.name,
+            note_type: self.note_type,
+            note,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            revision: self.revision,
+            expires_at: self.expires_at,
+            tags: self.tags,
+            compression: self.compression,
+            hash: self.hash,
+            links: self.links,
+            kind: self.kind,
+        }
+    }
+}
+
+impl From<NoteEntry> for NoteMeta {
+    fn from(entry: NoteEntry) -> Self {
+        NoteMeta {
+            writer: entry.writer,
+            topic: entry.topic,
+            sub_topic: entry.sub_topic,
+            name: entry.name,
+            note_type: entry.note_type,
+            created_at: entry.created_at,
+            updated_at: entry.updated_at,
+            revision: entry.revision,
+            expires_at: entry.expires_at,
+            tags: entry.tags,
+            compression: entry.compression,
+            hash: entry.hash,
+            links: entry.links,
+            kind: entry.kind,
+        }
+    }
+}
+
+/// a note body, shared by every note and history entry with the same hash
+#[cw_serde]
+pub struct NoteBody {
+    pub note: String,
+    /// number of notes and history entries holding this body
+    pub refs: u64,
+}
+
+/// the items of a list note are indexed `first..next`
+#[cw_serde]
+#[derive(Default)]
+pub struct ListState {
+    pub first: u64,
+    pub next: u64,
+    /// bytes held in the items
+    pub size: u64,
+}
+
+pub fn note_idx(_pk: &[u8], d: &NoteMeta) -> (String, String) {
+    (d.topic.clone(), d.sub_topic.clone())
+}
+
+/// notes that never expire sort last
+pub fn note_expiry_idx(_pk: &[u8], d: &NoteMeta) -> u64 {
+    d.expires_at.map(|t| t.nanos()).unwrap_or(u64::MAX)
+}
+
+pub fn note_hash_idx(_pk: &[u8], d: &NoteMeta) -> String {
+    d.hash.clone()
+}
+
+/// a MultiIndex can only hold one key per note, this holds one per tag.
+/// keyed by (tag, the note's joined primary key)
+pub struct TagIndex<'a> {
+    pub idx: Map<'a, (&'a str, &'a [u8]), Empty>,
+}
+
+impl<'a> Index<NoteMeta> for TagIndex<'a> {
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &NoteMeta) -> StdResult<()> {
+        for tag in &data.tags {
+            self.idx.save(store, (tag, pk), &Empty {})?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &NoteMeta) -> StdResult<()> {
+        for tag in &old_data.tags {
+            self.idx.remove(store, (tag, pk));
+        }
+        Ok(())
+    }
+}
+
+/// keyed by (the linked note, the linking note's joined primary key)
+pub struct LinkIndex<'a> {
+    pub idx: Map<'a, (NotePk<'a>, &'a [u8]), Empty>,
+}
+
+impl<'a> Index<NoteMeta> for LinkIndex<'a> {
+    fn save(&self, store: &mut dyn Storage, pk: &[u8], data: &NoteMeta) -> StdResult<()> {
+        for link in &data.links {
+            self.idx.save(store, ((&link.topic, &link.sub_topic, &link.name), pk), &Empty {})?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, store: &mut dyn Storage, pk: &[u8], old_data: &NoteMeta) -> StdResult<()> {
+        for link in &old_data.links {
+            self.idx.remove(store, ((&link.topic, &link.sub_topic, &link.name), pk));
+        }
+        Ok(())
+    }
 }
 
 pub struct NoteIndexes<'a> {
-    pub subtopic: MultiIndex<'a, String, NoteEntry, String>,
+    pub subtopic: MultiIndex<'a, (String, String), NoteMeta, NotePk<'a>>,
+    pub expiry: MultiIndex<'a, u64, NoteMeta, NotePk<'a>>,
+    pub tag: TagIndex<'a>,
+    pub hash: MultiIndex<'a, String, NoteMeta, NotePk<'a>>,
+    pub link: LinkIndex<'a>,
 }
 
-impl<'a> IndexList<NoteEntry> for NoteIndexes<'a> {
-    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<NoteEntry>> + '_> {
-        let v: Vec<&dyn Index<NoteEntry>> = vec![&self.subtopic];
+impl<'a> IndexList<NoteMeta> for NoteIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<NoteMeta>> + '_> {
+        let v: Vec<&dyn Index<NoteMeta>> =
+            vec![&self.subtopic, &self.expiry, &self.tag, &self.hash, &self.link];
         Box::new(v.into_iter())
     }
 }
 
-pub fn notes<'a>() -> IndexedMap<'a, String, NoteEntry, NoteIndexes<'a>> {
+pub fn notes<'a>() -> IndexedMap<'a, NotePk<'a>, NoteMeta, NoteIndexes<'a>> {
     IndexedMap::new(
         NOTE_KEY,
         NoteIndexes {
             subtopic: MultiIndex::new(note_idx, NOTE_KEY, NOTE_INDEX_KEY),
+            expiry: MultiIndex::new(note_expiry_idx, NOTE_KEY, NOTE_EXPIRY_INDEX_KEY),
+            tag: TagIndex {
+                idx: Map::new(NOTE_TAG_INDEX_KEY),
+            },
+            hash: MultiIndex::new(note_hash_idx, NOTE_KEY, NOTE_HASH_INDEX_KEY),
+            link: LinkIndex {
+                idx: Map::new(NOTE_LINK_INDEX_KEY),
+            },
         },
     )
 }
 
 pub const TOPICSMAP: Map<String, String> = Map::new(TOPICSET_KEY);
+pub const TOPIC_INFO: Map<&str, TopicInfo> = Map::new(TOPIC_INFO_KEY);
+pub const SUBTOPIC_INFO: Map<SubTopicPk, TopicInfo> = Map::new(SUBTOPIC_INFO_KEY);
+
+/// a removed note's meta. its body, history and items stay where they were
+#[cw_serde]
+pub struct TrashEntry {
+    pub meta: NoteMeta,
+    pub removed_by: Addr,
+    pub removed_at: NoteStamp,
+    pub purge_after: Timestamp,
+}
+
+pub fn trash_purge_idx(_pk: &[u8], d: &TrashEntry) -> u64 {
+    d.purge_after.nanos()
+}
+
+pub struct TrashIndexes<'a> {
+    pub purge: MultiIndex<'a, u64, TrashEntry, NotePk<'a>>,
+}
+
+impl<'a> IndexList<TrashEntry> for TrashIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<TrashEntry>> + '_> {
+        let v: Vec<&dyn Index<TrashEntry>> = vec![&self.purge];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn trash<'a>() -> IndexedMap<'a, NotePk<'a>, TrashEntry, TrashIndexes<'a>> {
+    IndexedMap::new(
+        TRASH_KEY,
+        TrashIndexes {
+            purge: MultiIndex::new(trash_purge_idx, TRASH_KEY, TRASH_PURGE_INDEX_KEY),
+        },
+    )
+}
+
+/// unpublished revisions, one per note. they hold a reference to their body
+pub const DRAFTS: Map<NotePk, NoteMeta> = Map::new(DRAFTS_KEY);
+
+pub fn alias_target_idx(_pk: &[u8], d: &NoteAlias) -> (String, String, String) {
+    (d.target.topic.clone(), d.target.sub_topic.clone(), d.target.name.clone())
+}
+
+pub struct AliasIndexes<'a> {
+    pub target: MultiIndex<'a, (String, String, String), NoteAlias, NotePk<'a>>,
+}
+
+impl<'a> IndexList<NoteAlias> for AliasIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<NoteAlias>> + '_> {
+        let v: Vec<&dyn Index<NoteAlias>> = vec![&self.target];
+        Box::new(v.into_iter())
+    }
+}
+
+/// aliases share their keys with `notes()`, a name is one or the other
+pub fn aliases<'a>() -> IndexedMap<'a, NotePk<'a>, NoteAlias, AliasIndexes<'a>> {
+    IndexedMap::new(
+        ALIASES_KEY,
+        AliasIndexes {
+            target: MultiIndex::new(alias_target_idx, ALIASES_KEY, ALIAS_TARGET_INDEX_KEY),
+        },
+    )
+}
+
+/// follows aliases from `key` to the name of a note. a name that is neither is returned as is
+pub fn resolve_alias(store: &dyn Storage, key: NoteKey) -> StdResult<NoteKey> {
+    let mut key = key;
+    let mut seen: Vec<NoteKey> = vec![];
+    while let Some(alias) = aliases().may_load(store, (&key.topic, &key.sub_topic, &key.name))? {
+        if seen.contains(&key) {
+            return Err(StdError::generic_err(format!("Alias loop at {}", key)));
+        }
+        if seen.len() >= MAX_ALIAS_DEPTH {
+            return Err(StdError::generic_err(format!(
+                "Alias {} is more than {} aliases from a note",
+                seen[0], MAX_ALIAS_DEPTH
+            )));
+        }
+        seen.push(key);
+        key = alias.target;
+    }
+    Ok(key)
+}
 
-// key is actually NoteKey
-//pub const NOTES: Map<String, NoteEntry> = Map::new(NOTES_KEY);
 pub const WRITER: Map<Addr, String> = Map::new(WRITERS_KEY);
+/// previous revisions of a note, keyed by note and revision
+pub const NOTE_HISTORY: Map<(NotePk, u64), NoteMeta> = Map::new(NOTE_HISTORY_KEY);
+/// note bodies keyed by their hash
+pub const NOTE_BODIES: Map<&str, NoteBody> = Map::new(NOTE_BODIES_KEY);
+pub const CONFIG: Item<Config> = Item::new(CONFIG_KEY);
+pub const UPLOADS: Map<NotePk, PendingUpload> = Map::new(UPLOADS_KEY);
+/// chunks of a pending upload, keyed by note and chunk index
+pub const UPLOAD_CHUNKS: Map<(NotePk, u32), String> = Map::new(UPLOAD_CHUNKS_KEY);
+pub const LISTS: Map<NotePk, ListState> = Map::new(LISTS_KEY);
+pub const LIST_ITEMS: Map<(NotePk, u64), ListItem> = Map::new(LIST_ITEMS_KEY);
+/// fields of a map note, keyed by note and field
+pub const MAP_FIELDS: Map<(NotePk, &str), NoteField> = Map::new(MAP_FIELDS_KEY);
+/// per topic overrides of `Config::max_note_size`
+pub const TOPIC_NOTE_SIZE: Map<&str, u32> = Map::new(TOPIC_NOTE_SIZE_KEY);
@@ -1,26 +1,86 @@
 // ----------------------------------- Tests -----------------------------------
 
+use std::str::FromStr;
+
 use cosmwasm_std::{
-    testing::{mock_dependencies, mock_env, mock_info},
-    Addr, StdError,
+    testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+    Addr, Deps, Env, Order, OwnedDeps, StdError, StdResult, Storage,
 };
 use cw2::ContractVersion;
 use cw_ownable::Ownership;
-use pfc_notes::{InstantiateMsg, NoteEntry, NoteType, NoteWriter};
+use cw_storage_plus::Map;
+use pfc_notes::{
+    codec, Compression, Config, InstantiateMsg, ListOrder, NoteEntry, NoteKey, NoteKind, NotePatch,
+    NoteStamp, NoteStatus, NoteType, NoteWriter, RemovalStatus, SubTopicKey, TopicInfo,
+};
 
 use crate::{
     error::ContractError,
     execute::{
-        exec_add_note, exec_add_writer, exec_rm_note, exec_rm_sub_topic, exec_rm_topic,
-        exec_rm_writer,
+        exec_abort_upload, exec_add_note, exec_add_writer, exec_append_to_note, exec_begin_upload,
+        exec_copy_sub_topic, exec_decrement_note, exec_discard_draft, exec_finalize_upload,
+        exec_increment_note, exec_move_note, exec_patch_note, exec_prune_expired,
+        exec_publish_note, exec_purge_trash, exec_remove_alias, exec_remove_note_field,
+        exec_rename_sub_topic, exec_rename_topic, exec_restore_note, exec_revert_note,
+        exec_rm_note, exec_rm_sub_topic, exec_rm_topic, exec_rm_writer, exec_set_alias,
+        exec_set_note_field, exec_set_sub_topic_info, exec_set_topic_info,
+        exec_set_topic_note_size, exec_trash_note, exec_update_config, exec_upload_chunk,
+        max_note_size, resolve_note_type, sha256_hex, NoteOptions,
     },
     instantiate,
-    query::{query_entries, query_note, query_sub_topics, query_topics, query_writer},
+    migrate::{migrate_config, migrate_storage_keys},
+    query::{
+        query_alias, query_backlinks, query_dangling_links, query_drafts, query_entries,
+        query_list_items, query_note, query_note_at_revision, query_note_chunk, query_note_fields,
+        query_note_hash, query_note_history, query_notes_by_hash, query_notes_by_tag,
+        query_removal_status, query_sub_topics, query_topics, query_trash, query_upload,
+        query_writer,
+    },
+    state::{CONFIG, NOTE_BODIES},
     CONTRACT_NAME, CONTRACT_VERSION,
 };
 
 //use super::*;
 
+/// the note, failing if it has expired
+fn live_note(
+    deps: Deps,
+    env: &Env,
+    topic: &str,
+    sub_topic: &str,
+    name: &str,
+) -> StdResult<NoteEntry> {
+    match query_note(deps, env, topic, sub_topic, name)? {
+        NoteStatus::Live(note) => Ok(*note),
+        NoteStatus::Expired {
+            key,
+            ..
+        } => Err(StdError::generic_err(format!("{} has expired", key))),
+    }
+}
+
+/// instantiates the contract with a single writer, `pie`
+fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+    let mut deps = mock_dependencies();
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("larry", &[]),
+        InstantiateMsg {
+            owner: "pumpkin".into(),
+            writers: vec![NoteWriter {
+                address: String::from("pie"),
+                name: String::from("pie name"),
+            }],
+            history_depth: None,
+            max_note_size: None,
+            trash_retention: None,
+        },
+    )
+    .unwrap();
+    deps
+}
+
 #[test]
 fn proper_initialization() {
     let mut deps = mock_dependencies();
@@ -33,6 +93,9 @@ fn proper_initialization() {
         InstantiateMsg {
             owner: "pumpkin".into(),
             writers: vec![],
+            history_depth: None,
+            max_note_size: None,
+            trash_retention: None,
         },
     )
     .unwrap();
@@ -80,6 +143,9 @@ fn writers() {
                     name: String::from("spiced"),
                 },
             ],
+            history_depth: None,
+            max_note_size: None,
+            trash_retention: None,
         },
     )
     .unwrap();
@@ -148,6 +214,9 @@ fn notes() {
                     name: String::from("spiced"),
                 },
             ],
+            history_depth: None,
+            max_note_size: None,
+            trash_retention: None,
         },
     )
     .unwrap();
@@ -155,12 +224,14 @@ fn notes() {
     // permissions
     let err = exec_add_note(
         deps.as_mut(),
+        &mock_env(),
         &Addr::unchecked("nope"),
         "topic",
         "sub",
         "name",
         NoteType::Ipfs,
         "ipfs://foobar",
+        NoteOptions::default(),
     )
     .unwrap_err();
     match err {
@@ -178,8 +249,8 @@ fn notes() {
             unreachable!("wrong error")
         },
     };
-    let err =
-        exec_rm_note(deps.as_mut(), &Addr::unchecked("nope"), "topic", "sub", "name").unwrap_err();
+    let err = exec_rm_note(deps.as_mut(), &Addr::unchecked("nope"), "topic", "sub", "name", None)
+        .unwrap_err();
     match err {
         ContractError::Std(std) => match std {
             StdError::NotFound {
@@ -198,15 +269,17 @@ fn notes() {
     // functionality
     exec_add_note(
         deps.as_mut(),
+        &mock_env(),
         &Addr::unchecked("pie"),
         "topic",
         "sub",
         "name",
         NoteType::Ipfs,
-        "ipfs://foobar",
+        "ipfs://QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG",
+        NoteOptions::default(),
     )
     .unwrap();
-    let note = query_note(deps.as_ref(), "topic", "sub", "name").unwrap();
+    let note = live_note(deps.as_ref(), &mock_env(), "topic", "sub", "name").unwrap();
     assert_eq!(
         note,
         NoteEntry {
@@ -215,10 +288,19 @@ fn notes() {
             sub_topic: "sub".to_string(),
             name: "name".to_string(),
             note_type: NoteType::Ipfs,
-            note: "ipfs://foobar".to_string(),
+            note: "ipfs://QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG".to_string(),
+            created_at: NoteStamp::from(&mock_env().block),
+            updated_at: NoteStamp::from(&mock_env().block),
+            revision: 1,
+            expires_at: None,
+            tags: vec![],
+            compression: Compression::None,
+            hash: sha256_hex("ipfs://QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG".as_bytes()),
+            links: vec![],
+            kind: NoteKind::Plain,
         }
     );
-    let err = query_note(deps.as_ref(), "topic", "sub", "Nope").unwrap_err();
+    let err = live_note(deps.as_ref(), &mock_env(), "topic", "sub", "Nope").unwrap_err();
     match err {
         StdError::NotFound {
             ..
@@ -229,7 +311,7 @@ fn notes() {
         },
     }
 
-    let err = query_note(deps.as_ref(), "topic", "nope", "name").unwrap_err();
+    let err = live_note(deps.as_ref(), &mock_env(), "topic", "nope", "name").unwrap_err();
     match err {
         StdError::NotFound {
             ..
@@ -241,32 +323,38 @@ fn notes() {
     }
     exec_add_note(
         deps.as_mut(),
+        &mock_env(),
         &Addr::unchecked("pie"),
         "topic",
         "sub2",
         "name",
         NoteType::String,
         "string",
+        NoteOptions::default(),
     )
     .unwrap();
     exec_add_note(
         deps.as_mut(),
+        &mock_env(),
         &Addr::unchecked("pie"),
         "topic2",
         "sub",
         "name",
         NoteType::Json,
         "{\"foo\":\"bar\"}",
+        NoteOptions::default(),
     )
     .unwrap();
     exec_add_note(
         deps.as_mut(),
+        &mock_env(),
         &Addr::unchecked("pie"),
         "topic2",
         "sub",
         "name2",
         NoteType::String,
         "A String walks into a bar",
+        NoteOptions::default(),
     )
     .unwrap();
     let topics = query_topics(deps.as_ref(), None, None).unwrap();
@@ -283,19 +371,2710 @@ fn notes() {
     let subs = query_sub_topics(deps.as_ref(), "topic", Some("sub".into()), None).unwrap();
     assert_eq!(subs.entries.len(), 1);
 
-    let notes = query_entries(deps.as_ref(), "topic2", "sub", None, None).unwrap();
+    let notes = query_entries(deps.as_ref(), &mock_env(), "topic2", "sub", None, None).unwrap();
     assert_eq!(notes.entries.len(), 2);
 
     let notes =
-        query_entries(deps.as_ref(), "topic2", "sub", Some("name".to_string()), None).unwrap();
+        query_entries(deps.as_ref(), &mock_env(), "topic2", "sub", Some("name".to_string()), None)
+            .unwrap();
     assert_eq!(notes.entries.len(), 1);
 
-    exec_rm_note(deps.as_mut(), &Addr::unchecked("pie"), "topic2", "sub", "name").unwrap();
-    exec_rm_note(deps.as_mut(), &Addr::unchecked("pie"), "topic2", "sub", "name2").unwrap();
-    exec_rm_sub_topic(deps.as_mut(), &Addr::unchecked("pie"), "topic2", "sub").unwrap();
+    exec_rm_note(deps.as_mut(), &Addr::unchecked("pie"), "topic2", "sub", "name", None).unwrap();
+    exec_rm_note(deps.as_mut(), &Addr::unchecked("pie"), "topic2", "sub", "name2", None).unwrap();
+    exec_rm_sub_topic(deps.as_mut(), &Addr::unchecked("pie"), "topic2", "sub", None).unwrap();
     let subs = query_sub_topics(deps.as_ref(), "topic2", None, None).unwrap();
     assert_eq!(subs.entries.len(), 0);
-    exec_rm_topic(deps.as_mut(), &Addr::unchecked("pie"), "topic2").unwrap();
+    exec_rm_topic(deps.as_mut(), &Addr::unchecked("pie"), "topic2", None).unwrap();
     let topics = query_topics(deps.as_ref(), None, None).unwrap();
     assert_eq!(topics.entries.len(), 1);
 }
+
+#[test]
+fn note_stamps() {
+    let mut deps = setup();
+    let first = mock_env();
+    exec_add_note(
+        deps.as_mut(),
+        &first,
+        &Addr::unchecked("pie"),
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "v1",
+        NoteOptions::default(),
+    )
+    .unwrap();
+
+    let mut later = mock_env();
+    later.block.height += 100;
+    later.block.time = later.block.time.plus_seconds(600);
+    exec_add_note(
+        deps.as_mut(),
+        &later,
+        &Addr::unchecked("pie"),
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "v2",
+        NoteOptions::default(),
+    )
+    .unwrap();
+
+    let note = live_note(deps.as_ref(), &mock_env(), "topic", "sub", "name").unwrap();
+    assert_eq!(note.note, "v2");
+    assert_eq!(note.created_at, NoteStamp::from(&first.block));
+    assert_eq!(note.updated_at, NoteStamp::from(&later.block));
+    assert_eq!(note.revision, 2);
+
+    let entries = query_entries(deps.as_ref(), &mock_env(), "topic", "sub", None, None).unwrap();
+    assert_eq!(entries.entries, vec![note]);
+
+    // removing and re-adding starts the note again
+    exec_rm_note(deps.as_mut(), &Addr::unchecked("pie"), "topic", "sub", "name", None).unwrap();
+    exec_add_note(
+        deps.as_mut(),
+        &later,
+        &Addr::unchecked("pie"),
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "v3",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    let note = live_note(deps.as_ref(), &mock_env(), "topic", "sub", "name").unwrap();
+    assert_eq!(note.created_at, NoteStamp::from(&later.block));
+    assert_eq!(note.revision, 1);
+}
+
+#[test]
+fn migrate_legacy_notes() {
+    let mut deps = mock_dependencies();
+    let legacy_notes = Map::<String, NoteEntry>::new("topic_subtopics_notes_001");
+    let legacy_subtopics = Map::<String, SubTopicKey>::new("topic_subtopics_001");
+    // written before notes were stamped
+    deps.storage.set(
+        &legacy_notes.key("topic_sub:name".to_string()),
+        br#"{"writer":"pie","topic":"topic","sub_topic":"sub","name":"name","note_type":"string","note":"old"}"#,
+    );
+    let stamped = NoteEntry {
+        writer: Addr::unchecked("pie"),
+        topic: "topic_sub".to_string(),
+        sub_topic: "x".to_string(),
+        name: "name".to_string(),
+        note_type: NoteType::String,
+        note: "stamped".to_string(),
+        created_at: NoteStamp::from(&mock_env().block),
+        updated_at: NoteStamp::from(&mock_env().block),
+        revision: 3,
+        expires_at: None,
+        tags: vec![],
+        compression: Compression::None,
+        hash: sha256_hex(b"stamped"),
+        links: vec![],
+        kind: NoteKind::Plain,
+    };
+    legacy_notes.save(deps.as_mut().storage, "topic_sub_x:name".to_string(), &stamped).unwrap();
+    for (topic, sub_topic) in [("topic", "sub"), ("topic_sub", "x")] {
+        let subtopic_key = SubTopicKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+        };
+        legacy_subtopics
+            .save(deps.as_mut().storage, subtopic_key.to_string(), &subtopic_key)
+            .unwrap();
+    }
+
+    let mut env = mock_env();
+    env.block.height += 5;
+    // two subtopics and a note fit the first batch, the other note the second
+    assert_eq!(
+        migrate_storage_keys(deps.as_mut().storage, &env.block, Some(3)).unwrap(),
+        (1, false)
+    );
+    assert_eq!(
+        migrate_storage_keys(deps.as_mut().storage, &env.block, Some(3)).unwrap(),
+        (1, true)
+    );
+    let note = live_note(deps.as_ref(), &mock_env(), "topic", "sub", "name").unwrap();
+    assert_eq!(
+        note,
+        NoteEntry {
+            writer: Addr::unchecked("pie"),
+            topic: "topic".to_string(),
+            sub_topic: "sub".to_string(),
+            name: "name".to_string(),
+            note_type: NoteType::String,
+            note: "old".to_string(),
+            created_at: NoteStamp::from(&env.block),
+            updated_at: NoteStamp::from(&env.block),
+            revision: 1,
+            expires_at: None,
+            tags: vec![],
+            compression: Compression::None,
+            hash: sha256_hex(b"old"),
+            links: vec![],
+            kind: NoteKind::Plain,
+        }
+    );
+    let note = live_note(deps.as_ref(), &mock_env(), "topic_sub", "x", "name").unwrap();
+    assert_eq!(note, stamped);
+    let subs = query_sub_topics(deps.as_ref(), "topic", None, None).unwrap();
+    assert_eq!(subs.entries.len(), 1);
+    let entries = query_entries(deps.as_ref(), &mock_env(), "topic", "sub", None, None).unwrap();
+    assert_eq!(entries.entries.len(), 1);
+
+    // the legacy maps are gone, so migrating again does nothing
+    assert!(legacy_notes.is_empty(deps.as_ref().storage));
+    assert!(legacy_subtopics.is_empty(deps.as_ref().storage));
+    assert_eq!(
+        migrate_storage_keys(deps.as_mut().storage, &mock_env().block, None).unwrap(),
+        (0, true)
+    );
+}
+
+#[test]
+fn colliding_keys() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+
+    // these all flattened to "a_b_c:n" with string keys
+    for (topic, sub_topic) in [("a_b", "c"), ("a", "b_c")] {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            topic,
+            sub_topic,
+            "n",
+            NoteType::String,
+            topic,
+            NoteOptions::default(),
+        )
+        .unwrap();
+    }
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "a",
+        "b",
+        "c:n",
+        NoteType::String,
+        "other",
+        NoteOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(live_note(deps.as_ref(), &env, "a_b", "c", "n").unwrap().note, "a_b");
+    assert_eq!(live_note(deps.as_ref(), &env, "a", "b_c", "n").unwrap().note, "a");
+    let entries = query_entries(deps.as_ref(), &env, "a", "b", None, None).unwrap();
+    assert_eq!(entries.entries.len(), 1);
+    assert_eq!(entries.entries[0].name, "c:n");
+    let entries = query_entries(deps.as_ref(), &env, "a_b", "c", None, None).unwrap();
+    assert_eq!(entries.entries.len(), 1);
+    let subs = query_sub_topics(deps.as_ref(), "a", None, None).unwrap();
+    assert_eq!(subs.entries.into_iter().map(|s| s.sub_topic).collect::<Vec<_>>(), vec!["b", "b_c"]);
+    let subs = query_sub_topics(deps.as_ref(), "a", Some("b".to_string()), None).unwrap();
+    assert_eq!(subs.entries.len(), 1);
+}
+
+#[test]
+fn note_expiry() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "both",
+        NoteType::String,
+        "nope",
+        NoteOptions {
+            expires_at: Some(env.block.time.plus_seconds(10)),
+            ttl_seconds: Some(10),
+            expected_revision: None,
+            tags: None,
+            compression: None,
+            links: None,
+            draft: false,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ConflictingExpiry));
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "past",
+        NoteType::String,
+        "nope",
+        NoteOptions {
+            expires_at: Some(env.block.time),
+            ttl_seconds: None,
+            expected_revision: None,
+            tags: None,
+            compression: None,
+            links: None,
+            draft: false,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ExpiryInPast(_)));
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "far",
+        NoteType::String,
+        "nope",
+        NoteOptions {
+            ttl_seconds: Some(u64::MAX),
+            ..NoteOptions::default()
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::TtlTooLarge(u64::MAX)));
+
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "short",
+        NoteType::String,
+        "short lived",
+        NoteOptions {
+            expires_at: None,
+            ttl_seconds: Some(60),
+            expected_revision: None,
+            tags: None,
+            compression: None,
+            links: None,
+            draft: false,
+        },
+    )
+    .unwrap();
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "long",
+        NoteType::String,
+        "long lived",
+        NoteOptions {
+            expires_at: Some(env.block.time.plus_seconds(3600)),
+            ttl_seconds: None,
+            expected_revision: None,
+            tags: None,
+            compression: None,
+            links: None,
+            draft: false,
+        },
+    )
+    .unwrap();
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "forever",
+        NoteType::String,
+        "never expires",
+        NoteOptions::default(),
+    )
+    .unwrap();
+
+    let note = live_note(deps.as_ref(), &env, "topic", "sub", "short").unwrap();
+    assert_eq!(note.expires_at, Some(env.block.time.plus_seconds(60)));
+    let entries = query_entries(deps.as_ref(), &env, "topic", "sub", None, None).unwrap();
+    assert_eq!(entries.entries.len(), 3);
+
+    let mut later = mock_env();
+    later.block.time = later.block.time.plus_seconds(60);
+    let status = query_note(deps.as_ref(), &later, "topic", "sub", "short").unwrap();
+    assert_eq!(
+        status,
+        NoteStatus::Expired {
+            key: NoteKey {
+                topic: "topic".to_string(),
+                sub_topic: "sub".to_string(),
+                name: "short".to_string(),
+            },
+            expired_at: later.block.time,
+        }
+    );
+    let entries = query_entries(deps.as_ref(), &later, "topic", "sub", None, None).unwrap();
+    assert_eq!(
+        entries.entries.into_iter().map(|e| e.name).collect::<Vec<_>>(),
+        vec!["forever", "long"]
+    );
+
+    // only expired notes are pruned
+    let res = exec_prune_expired(deps.as_mut(), &later, &pie, None).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    let err = live_note(deps.as_ref(), &later, "topic", "sub", "short").unwrap_err();
+    assert!(matches!(err, StdError::NotFound { .. }));
+
+    let mut much_later = mock_env();
+    much_later.block.time = much_later.block.time.plus_seconds(86400);
+    let res = exec_prune_expired(deps.as_mut(), &much_later, &pie, Some(10)).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    let entries = query_entries(deps.as_ref(), &much_later, "topic", "sub", None, None).unwrap();
+    assert_eq!(entries.entries.len(), 1);
+
+    // a batch is cut short by the entries it removes, each note's history included
+    exec_update_config(deps.as_mut(), Some(50), None, None).unwrap();
+    for i in 0..21 {
+        for revision in 0..51 {
+            exec_add_note(
+                deps.as_mut(),
+                &env,
+                &pie,
+                "topic",
+                "kept",
+                &format!("n{:02}", i),
+                NoteType::String,
+                &revision.to_string(),
+                NoteOptions {
+                    ttl_seconds: Some(60),
+                    ..NoteOptions::default()
+                },
+            )
+            .unwrap();
+        }
+    }
+    let res = exec_prune_expired(deps.as_mut(), &much_later, &pie, Some(50)).unwrap();
+    assert_eq!(res.attributes[0].value, "20");
+    let res = exec_prune_expired(deps.as_mut(), &much_later, &pie, Some(50)).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+
+    // a page looks at a bounded number of notes, expired or not, and says where to go on from
+    for i in 0..120 {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "topic",
+            "many",
+            &format!("n{:03}", i),
+            NoteType::String,
+            "short lived",
+            NoteOptions {
+                ttl_seconds: Some(60),
+                ..NoteOptions::default()
+            },
+        )
+        .unwrap();
+    }
+    let page = query_entries(deps.as_ref(), &much_later, "topic", "many", None, None).unwrap();
+    assert!(page.entries.is_empty());
+    assert_eq!(page.start_after.as_deref(), Some("n099"));
+    let page =
+        query_entries(deps.as_ref(), &much_later, "topic", "many", page.start_after, None).unwrap();
+    assert!(page.entries.is_empty());
+    assert_eq!(page.start_after, None);
+    let page = query_entries(deps.as_ref(), &env, "topic", "many", None, Some(5)).unwrap();
+    assert_eq!((page.entries.len(), page.start_after.as_deref()), (5, Some("n004")));
+}
+
+#[test]
+fn note_history() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+
+    let err = exec_update_config(deps.as_mut(), Some(51), None, None).unwrap_err();
+    assert!(matches!(err, ContractError::HistoryDepthTooLarge(51, 50)));
+    exec_update_config(deps.as_mut(), Some(2), None, None).unwrap();
+
+    for body in ["v1", "v2", "v3", "v4"] {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "topic",
+            "sub",
+            "name",
+            NoteType::String,
+            body,
+            NoteOptions::default(),
+        )
+        .unwrap();
+    }
+    let history = query_note_history(deps.as_ref(), "topic", "sub", "name", None, None).unwrap();
+    assert_eq!(
+        history.entries.iter().map(|e| (e.revision, e.note.as_str())).collect::<Vec<_>>(),
+        vec![(2, "v2"), (3, "v3")]
+    );
+    let history = query_note_history(deps.as_ref(), "topic", "sub", "name", Some(2), None).unwrap();
+    assert_eq!(history.entries.len(), 1);
+
+    let note = query_note_at_revision(deps.as_ref(), "topic", "sub", "name", 4).unwrap();
+    assert_eq!(note.note, "v4");
+    let note = query_note_at_revision(deps.as_ref(), "topic", "sub", "name", 2).unwrap();
+    assert_eq!(note.note, "v2");
+    query_note_at_revision(deps.as_ref(), "topic", "sub", "name", 1).unwrap_err();
+
+    let err = exec_revert_note(deps.as_mut(), &env, &pie, "topic", "sub", "name", 1).unwrap_err();
+    assert!(matches!(err, ContractError::RevisionNotFound(_, 1)));
+    exec_revert_note(deps.as_mut(), &env, &pie, "topic", "sub", "name", 2).unwrap();
+    let note = live_note(deps.as_ref(), &env, "topic", "sub", "name").unwrap();
+    assert_eq!(note.note, "v2");
+    assert_eq!(note.revision, 5);
+    let history = query_note_history(deps.as_ref(), "topic", "sub", "name", None, None).unwrap();
+    assert_eq!(
+        history.entries.iter().map(|e| (e.revision, e.note.as_str())).collect::<Vec<_>>(),
+        vec![(3, "v3"), (4, "v4")]
+    );
+
+    // the history goes with the note
+    exec_rm_note(deps.as_mut(), &pie, "topic", "sub", "name", None).unwrap();
+    let history = query_note_history(deps.as_ref(), "topic", "sub", "name", None, None).unwrap();
+    assert!(history.entries.is_empty());
+}
+
+#[test]
+fn note_expected_revision() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let expecting = |expected_revision| NoteOptions {
+        expected_revision,
+        ..NoteOptions::default()
+    };
+
+    // 0 means the note must be new
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "cron",
+        expecting(Some(0)),
+    )
+    .unwrap();
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "admin",
+        expecting(Some(0)),
+    )
+    .unwrap_err();
+    match err {
+        ContractError::RevisionMismatch {
+            note,
+            expected,
+            actual,
+        } => {
+            assert_eq!(note, r#""topic"/"sub"/"name""#);
+            assert_eq!(expected, 0);
+            assert_eq!(actual, 1);
+        },
+        _ => {
+            eprintln!("{:?}", err);
+            unreachable!("wrong error")
+        },
+    }
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "admin",
+        expecting(Some(1)),
+    )
+    .unwrap();
+    // the cron job lost the race
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "cron again",
+        expecting(Some(1)),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::RevisionMismatch { .. }));
+    assert_eq!(live_note(deps.as_ref(), &env, "topic", "sub", "name").unwrap().note, "admin");
+
+    let err = exec_rm_note(deps.as_mut(), &pie, "topic", "sub", "name", Some(1)).unwrap_err();
+    assert!(matches!(err, ContractError::RevisionMismatch { .. }));
+    exec_rm_note(deps.as_mut(), &pie, "topic", "sub", "name", Some(2)).unwrap();
+}
+
+#[test]
+fn cascading_removal() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    for sub_topic in ["sub1", "sub2"] {
+        for name in ["a", "b", "c"] {
+            exec_add_note(
+                deps.as_mut(),
+                &env,
+                &pie,
+                "topic",
+                sub_topic,
+                name,
+                NoteType::String,
+                "body",
+                NoteOptions::default(),
+            )
+            .unwrap();
+        }
+    }
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "other",
+        "sub1",
+        "a",
+        NoteType::String,
+        "body",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    exec_set_alias(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub2",
+        "latest",
+        NoteKey {
+            topic: "other".to_string(),
+            sub_topic: "sub1".to_string(),
+            name: "a".to_string(),
+        },
+    )
+    .unwrap();
+
+    // subtopic removal is paginated
+    let res = exec_rm_sub_topic(deps.as_mut(), &pie, "topic", "sub1", Some(2)).unwrap();
+    assert_eq!(res.attributes[1].value, "false");
+    let status = query_removal_status(deps.as_ref(), "topic", Some("sub1".into())).unwrap();
+    assert_eq!(
+        status,
+        RemovalStatus {
+            sub_topics: 1,
+            notes: 1,
+            aliases: 0,
+            trashed: 0,
+            drafts: 0,
+            uploads: 0,
+        }
+    );
+    let res = exec_rm_sub_topic(deps.as_mut(), &pie, "topic", "sub1", Some(2)).unwrap();
+    assert_eq!(res.attributes[1].value, "true");
+    let status = query_removal_status(deps.as_ref(), "topic", Some("sub1".into())).unwrap();
+    assert_eq!(
+        status,
+        RemovalStatus {
+            sub_topics: 0,
+            notes: 0,
+            aliases: 0,
+            trashed: 0,
+            drafts: 0,
+            uploads: 0,
+        }
+    );
+    live_note(deps.as_ref(), &env, "topic", "sub1", "c").unwrap_err();
+    let subs = query_sub_topics(deps.as_ref(), "topic", None, None).unwrap();
+    assert_eq!(subs.entries.len(), 1);
+
+    // topic removal takes notes first, then aliases and the trash, then subtopics, then the topic
+    exec_trash_note(deps.as_mut(), &env, &pie, "topic", "sub2", "a", None).unwrap();
+    let status = query_removal_status(deps.as_ref(), "topic", None).unwrap();
+    assert_eq!(
+        status,
+        RemovalStatus {
+            sub_topics: 1,
+            notes: 2,
+            aliases: 1,
+            trashed: 1,
+            drafts: 0,
+            uploads: 0,
+        }
+    );
+    let res = exec_rm_topic(deps.as_mut(), &pie, "topic", Some(3)).unwrap();
+    assert_eq!(res.attributes[2].value, "false");
+    let topics = query_topics(deps.as_ref(), None, None).unwrap();
+    assert_eq!(
+        topics.entries.into_iter().map(|t| t.topic).collect::<Vec<_>>(),
+        vec!["other", "topic"]
+    );
+    let res = exec_rm_topic(deps.as_mut(), &pie, "topic", Some(3)).unwrap();
+    assert_eq!(res.attributes[1].value, "1");
+    assert_eq!(res.attributes[2].value, "true");
+    assert_eq!(res.attributes[4].value, "1");
+    assert!(query_trash(deps.as_ref(), None, None).unwrap().entries.is_empty());
+    let status = query_removal_status(deps.as_ref(), "topic", None).unwrap();
+    assert_eq!(
+        status,
+        RemovalStatus {
+            sub_topics: 0,
+            notes: 0,
+            aliases: 0,
+            trashed: 0,
+            drafts: 0,
+            uploads: 0,
+        }
+    );
+    let topics = query_topics(deps.as_ref(), None, None).unwrap();
+    assert_eq!(topics.entries.into_iter().map(|t| t.topic).collect::<Vec<_>>(), vec!["other"]);
+    live_note(deps.as_ref(), &env, "other", "sub1", "a").unwrap();
+
+    // batches are cut short by the entries they remove, not just the notes
+    for i in 0..1000 {
+        exec_append_to_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "other",
+            "sub1",
+            "a-log",
+            Some(NoteType::String),
+            &i.to_string(),
+            None,
+        )
+        .unwrap();
+    }
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "other",
+        "sub1",
+        "b",
+        NoteType::String,
+        "body",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    let res = exec_rm_sub_topic(deps.as_mut(), &pie, "other", "sub1", None).unwrap();
+    assert_eq!(
+        (res.attributes[0].value.as_str(), res.attributes[1].value.as_str()),
+        ("2", "false")
+    );
+    live_note(deps.as_ref(), &env, "other", "sub1", "b").unwrap();
+    let res = exec_rm_topic(deps.as_mut(), &pie, "other", None).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    assert_eq!(res.attributes[2].value, "true");
+    assert!(query_topics(deps.as_ref(), None, None).unwrap().entries.is_empty());
+}
+
+#[test]
+fn note_validation() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let mut add = |note_type: NoteType, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "topic",
+            "sub",
+            "name",
+            note_type,
+            note,
+            NoteOptions::default(),
+        )
+    };
+
+    add(NoteType::Json, r#"{"foo":["bar",1,null]}"#).unwrap();
+    add(NoteType::Json, "42").unwrap();
+    // numbers are checked but never read as floats
+    add(NoteType::Json, r#"{"rate":-0.05,"max":1.5E+400,"s":"\u00e9\ud83d\ude00"}"#).unwrap();
+    for invalid in [r#"{"foo":"#, "01", "1.", "[1,]", r#""\ud800""#, "{} {}", "\"\t\""] {
+        let err = add(NoteType::Json, invalid).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidJson(_)), "{}", invalid);
+    }
+    let err = add(NoteType::Json, "ewogICAgIm1haW5uZXQiOiBbCiAgICAgICAgMTUwMiwK").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidJson(_)));
+
+    add(NoteType::Base64, "ewogICAgIm1haW5uZXQiOiBbCiAgICAgICAgMTUwMiwK").unwrap();
+    let err = add(NoteType::Base64, "not base64!").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidBase64(_)));
+
+    for cid in [
+        "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG",
+        "ipfs://QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG/readme",
+        "/ipfs/bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi",
+        "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/wiki/index.html",
+        "BAFYBEIGDYRZT5SFP7UDM7HU76UH7Y26NF3EFUYLQABF3OCLGTQY55FBZDI",
+        "f01701220c3c4733ec8affd06cf9e9ff50ffc6bcd2ec85a6170004bb709669c31de94391a",
+        "zdj7WWeQ43G6JJvLWQWZpyHuAMq6uYWRjkBXFad11vE2LHhQ7",
+    ] {
+        add(NoteType::Ipfs, cid).unwrap();
+    }
+    for cid in [
+        "ipfs://foobar",
+        "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbd0",
+        "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzd",
+        "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdI",
+        "",
+    ] {
+        let err = add(NoteType::Ipfs, cid).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIpfsCid(_)), "{}", cid);
+    }
+
+    add(NoteType::String, "anything { goes").unwrap();
+}
+
+#[test]
+fn note_types() {
+    for note_type in [
+        NoteType::Json,
+        NoteType::String,
+        NoteType::Ipfs,
+        NoteType::Base64,
+        NoteType::Markdown,
+        NoteType::Url,
+        NoteType::Arweave,
+        NoteType::Binary {
+            mime_type: "image/svg+xml".to_string(),
+        },
+    ] {
+        assert_eq!(NoteType::from_str(&note_type.to_string()), Ok(note_type));
+    }
+    assert_eq!(NoteType::from_str("JSON"), Ok(NoteType::Json));
+    assert_eq!(NoteType::from_str("Nope"), Err(()));
+
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let mut add = |note_type: NoteType, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "topic",
+            "sub",
+            "name",
+            note_type,
+            note,
+            NoteOptions::default(),
+        )
+    };
+
+    add(NoteType::Markdown, "# Changelog\n\n* fixed\ttabs\r\n").unwrap();
+    let err = add(NoteType::Markdown, "bell\u{7}").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMarkdown(_)));
+
+    add(NoteType::Url, "https://PFC.zone/notes?x=1#top").unwrap();
+    add(NoteType::Url, "wss://rpc.example.com:443").unwrap();
+    for url in ["PFC.zone", "https://", "https://pfc zone", "1http://pfc.zone"] {
+        let err = add(NoteType::Url, url).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidUrl(_)), "{}", url);
+    }
+
+    add(NoteType::Arweave, "bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U").unwrap();
+    add(NoteType::Arweave, "ar://bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U").unwrap();
+    let err = add(NoteType::Arweave, "bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidArweaveId(_)));
+
+    let png = |mime_type: &str| NoteType::Binary {
+        mime_type: mime_type.to_string(),
+    };
+    add(png("image/png"), "iVBORw0KGgo=").unwrap();
+    let err = add(png("image/png"), "not base64!").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidBase64(_)));
+    for mime_type in ["image", "image/", "/png", "image/png; charset=x", "image/p ng"] {
+        let err = add(png(mime_type), "iVBORw0KGgo=").unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMimeType(_)), "{}", mime_type);
+    }
+}
+
+#[test]
+fn note_tags() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let tagged = |tags: &[&str]| NoteOptions {
+        tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+        ..NoteOptions::default()
+    };
+
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "body",
+        tagged(&["mainnet", "has space"]),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidTag(_)));
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "topic",
+        "sub",
+        "name",
+        NoteType::String,
+        "body",
+        tagged(&["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::TooManyTags(11, 10)));
+
+    for (topic, name, tags) in [
+        ("station", "proposals", &["mainnet", "governance", "mainnet"][..]),
+        ("station", "old", &["deprecated", "mainnet"][..]),
+        ("other", "a", &["governance"][..]),
+    ] {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            topic,
+            "phoenix-1",
+            name,
+            NoteType::String,
+            "body",
+            tagged(tags),
+        )
+        .unwrap();
+    }
+    let note = live_note(deps.as_ref(), &env, "station", "phoenix-1", "proposals").unwrap();
+    assert_eq!(note.tags, vec!["governance", "mainnet"]);
+
+    let names = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, tag, start_after| {
+        query_notes_by_tag(deps.as_ref(), &env, tag, start_after, None)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|e| format!("{}/{}", e.topic, e.name))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&deps, "mainnet", None), vec!["station/old", "station/proposals"]);
+    assert_eq!(names(&deps, "governance", None), vec!["other/a", "station/proposals"]);
+    let start_after = NoteKey {
+        topic: "other".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: "a".to_string(),
+    };
+    assert_eq!(names(&deps, "governance", Some(start_after)), vec!["station/proposals"]);
+
+    // overwriting without tags keeps them, setting them replaces them
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "phoenix-1",
+        "old",
+        NoteType::String,
+        "body",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(names(&deps, "deprecated", None), vec!["station/old"]);
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "phoenix-1",
+        "old",
+        NoteType::String,
+        "body",
+        tagged(&[]),
+    )
+    .unwrap();
+    assert!(names(&deps, "deprecated", None).is_empty());
+    assert_eq!(names(&deps, "mainnet", None), vec!["station/proposals"]);
+
+    exec_rm_note(deps.as_mut(), &pie, "station", "phoenix-1", "proposals", None).unwrap();
+    assert!(names(&deps, "mainnet", None).is_empty());
+}
+
+#[test]
+fn note_size_limits() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    assert_eq!(max_note_size(deps.as_ref().storage, "topic").unwrap(), 32 * 1024);
+
+    exec_update_config(deps.as_mut(), None, Some(10), None).unwrap();
+    exec_set_topic_note_size(deps.as_mut(), "big", Some(20)).unwrap();
+    assert_eq!(max_note_size(deps.as_ref().storage, "topic").unwrap(), 10);
+    assert_eq!(max_note_size(deps.as_ref().storage, "big").unwrap(), 20);
+
+    let mut add = |topic: &str, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            topic,
+            "sub",
+            "name",
+            NoteType::String,
+            note,
+            NoteOptions::default(),
+        )
+    };
+    add("topic", "0123456789").unwrap();
+    let err = add("topic", "0123456789a").unwrap_err();
+    match err {
+        ContractError::NoteTooLarge {
+            limit,
+            size,
+        } => {
+            assert_eq!(limit, 10);
+            assert_eq!(size, 11);
+        },
+        _ => {
+            eprintln!("{:?}", err);
+            unreachable!("wrong error")
+        },
+    }
+    assert_eq!(err.to_string(), "Note is too large: 11 bytes, the limit is 10 bytes");
+    add("big", "0123456789a").unwrap();
+
+    exec_set_topic_note_size(deps.as_mut(), "big", None).unwrap();
+    assert_eq!(max_note_size(deps.as_ref().storage, "big").unwrap(), 10);
+
+    // a limit of zero would refuse every note
+    let err = exec_set_topic_note_size(deps.as_mut(), "big", Some(0)).unwrap_err();
+    assert!(matches!(err, ContractError::NoteSizeZero));
+    let err = exec_update_config(deps.as_mut(), None, Some(0), None).unwrap_err();
+    assert!(matches!(err, ContractError::NoteSizeZero));
+
+    // the override goes with the topic
+    exec_set_topic_note_size(deps.as_mut(), "big", Some(20)).unwrap();
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "big",
+        "sub",
+        "name",
+        NoteType::String,
+        "0123456789a",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    let res = exec_rm_topic(deps.as_mut(), &pie, "big", None).unwrap();
+    assert_eq!(res.attributes[2].value, "true");
+    assert_eq!(max_note_size(deps.as_ref().storage, "big").unwrap(), 10);
+}
+
+#[test]
+fn migrate_old_config() {
+    let mut deps = mock_dependencies();
+    deps.storage.set(b"config_001", br#"{"history_depth":3}"#);
+    migrate_config(deps.as_mut().storage).unwrap();
+    assert_eq!(
+        CONFIG.load(deps.as_ref().storage).unwrap(),
+        Config {
+            history_depth: 3,
+            max_note_size: 32 * 1024,
+            trash_retention: 30 * 24 * 60 * 60,
+        }
+    );
+}
+
+#[test]
+fn chunked_upload() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    exec_update_config(deps.as_mut(), None, Some(100), None).unwrap();
+
+    // the whole note has to fit the limit, which the topic override raises
+    let chunks = ["é".repeat(50), "b".repeat(100), "c".repeat(10)];
+    let body = chunks.concat();
+    let total_size = body.len() as u32;
+    let hash = sha256_hex(body.as_bytes());
+    let begin = |deps: &mut OwnedDeps<_, _, _>, total_size: u32, sha256: &str| {
+        exec_begin_upload(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "big",
+            "sub",
+            "name",
+            Some(NoteType::String),
+            3,
+            total_size,
+            sha256,
+        )
+    };
+
+    let err = exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 0, "a").unwrap_err();
+    assert_eq!(err.to_string(), r#"No upload in progress for note "big"/"sub"/"name""#);
+    let err = begin(&mut deps, total_size, &hash).unwrap_err();
+    assert_eq!(err.to_string(), "Note is too large: 210 bytes, the limit is 100 bytes");
+    exec_set_topic_note_size(deps.as_mut(), "big", Some(total_size)).unwrap();
+    let err = begin(&mut deps, total_size, "abc").unwrap_err();
+    assert_eq!(err.to_string(), "Invalid sha256 hash: abc");
+    begin(&mut deps, total_size, &hash.to_uppercase()).unwrap();
+
+    let err = exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 3, "a").unwrap_err();
+    assert_eq!(err.to_string(), "Chunk 3 is out of range, the upload has 3 chunks");
+    // out of order, and a chunk may be sent again
+    for index in [2, 0, 0] {
+        exec_upload_chunk(
+            deps.as_mut(),
+            &pie,
+            "big",
+            "sub",
+            "name",
+            index,
+            &chunks[index as usize],
+        )
+        .unwrap();
+    }
+    let status = query_upload(deps.as_ref(), "big", "sub", "name").unwrap();
+    assert_eq!(status.received, vec![0, 2]);
+    assert_eq!(status.upload.sha256, hash);
+    assert_eq!(status.upload.received_size, 110);
+    let err = exec_finalize_upload(deps.as_mut(), &env, &pie, "big", "sub", "name").unwrap_err();
+    assert_eq!(err.to_string(), "Upload is missing 1 chunks");
+
+    // chunks can't add up to more than the size given
+    let err = exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 1, &"x".repeat(101))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Upload chunks add up to 211 bytes, 210 were expected");
+    exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 1, "x").unwrap();
+    let err = exec_finalize_upload(deps.as_mut(), &env, &pie, "big", "sub", "name").unwrap_err();
+    assert_eq!(err.to_string(), "Upload chunks add up to 111 bytes, 210 were expected");
+    exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 1, &"x".repeat(100)).unwrap();
+    let err = exec_finalize_upload(deps.as_mut(), &env, &pie, "big", "sub", "name").unwrap_err();
+    assert!(matches!(err, ContractError::HashMismatch { .. }));
+
+    exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 1, &chunks[1]).unwrap();
+    let res = exec_finalize_upload(deps.as_mut(), &env, &pie, "big", "sub", "name").unwrap();
+    assert_eq!(res.attributes[0].value, hash);
+    assert_eq!(live_note(deps.as_ref(), &env, "big", "sub", "name").unwrap().note, body);
+    assert!(query_upload(deps.as_ref(), "big", "sub", "name").is_err());
+    let err = exec_finalize_upload(deps.as_mut(), &env, &pie, "big", "sub", "name").unwrap_err();
+    assert_eq!(err.to_string(), r#"No upload in progress for note "big"/"sub"/"name""#);
+
+    // removing the note drops an upload to it
+    begin(&mut deps, total_size, &hash).unwrap();
+    exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 0, &chunks[0]).unwrap();
+    exec_rm_note(deps.as_mut(), &pie, "big", "sub", "name", None).unwrap();
+    assert!(query_upload(deps.as_ref(), "big", "sub", "name").is_err());
+    begin(&mut deps, total_size, &hash).unwrap();
+    for (index, chunk) in chunks.iter().enumerate() {
+        exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", index as u32, chunk).unwrap();
+    }
+    exec_finalize_upload(deps.as_mut(), &env, &pie, "big", "sub", "name").unwrap();
+
+    // 64 byte pieces never split the two byte characters
+    let pieces = (0..4)
+        .map(|i| query_note_chunk(deps.as_ref(), &env, "big", "sub", "name", i, Some(64)).unwrap())
+        .collect::<Vec<_>>();
+    assert!(pieces.iter().all(|p| p.total_chunks == 4));
+    assert_eq!(pieces[0].data, "é".repeat(32));
+    assert_eq!(pieces.iter().map(|p| p.data.as_str()).collect::<String>(), body);
+    assert!(query_note_chunk(deps.as_ref(), &env, "big", "sub", "name", 4, Some(64)).is_err());
+
+    // a note with an upload in progress stays put until the upload finishes or is aborted
+    begin(&mut deps, total_size, &hash).unwrap();
+    exec_upload_chunk(deps.as_mut(), &pie, "big", "sub", "name", 0, &chunks[0]).unwrap();
+    let to = NoteKey::from(("big", "sub", "moved"));
+    let err = exec_move_note(deps.as_mut(), &pie, "big", "sub", "name", to.clone()).unwrap_err();
+    assert_eq!(err.to_string(), r#"Note "big"/"sub"/"name" has an upload in progress"#);
+    let err = exec_rename_sub_topic(deps.as_mut(), &pie, "big", "sub", "new", None).unwrap_err();
+    assert!(matches!(err, ContractError::UploadPending(_)));
+    let res = exec_abort_upload(deps.as_mut(), &pie, "big", "sub", "name").unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    assert!(query_upload(deps.as_ref(), "big", "sub", "name").is_err());
+    let err = exec_abort_upload(deps.as_mut(), &pie, "big", "sub", "name").unwrap_err();
+    assert!(matches!(err, ContractError::NoUpload(_)));
+    exec_move_note(deps.as_mut(), &pie, "big", "sub", "name", to).unwrap();
+
+    // the note type falls back to the topic's default
+    let begin_fresh = |deps: &mut OwnedDeps<_, _, _>| {
+        exec_begin_upload(deps.as_mut(), &env, &pie, "big", "sub", "fresh", None, 1, 1, &hash)
+    };
+    let err = begin_fresh(&mut deps).unwrap_err();
+    assert!(matches!(err, ContractError::NoteTypeRequired(_)));
+    let info = TopicInfo {
+        default_note_type: Some(NoteType::Markdown),
+        ..TopicInfo::default()
+    };
+    exec_set_topic_info(deps.as_mut(), &pie, "big", &info).unwrap();
+    begin_fresh(&mut deps).unwrap();
+    let status = query_upload(deps.as_ref(), "big", "sub", "fresh").unwrap();
+    assert_eq!(status.upload.note_type, NoteType::Markdown);
+
+    // uploads to names without a note are removed with the subtopic
+    let status = query_removal_status(deps.as_ref(), "big", Some("sub".into())).unwrap();
+    assert_eq!((status.notes, status.uploads), (1, 1));
+    let res = exec_rm_sub_topic(deps.as_mut(), &pie, "big", "sub", None).unwrap();
+    assert_eq!(res.attributes[1].value, "true");
+    assert_eq!(res.attributes[5].value, "1");
+    assert!(query_upload(deps.as_ref(), "big", "sub", "fresh").is_err());
+}
+
+#[test]
+fn note_compression() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let json = format!("[{}]", vec![r#"{"validator":"pfc","commission":"0.05"}"#; 50].join(","));
+    let mut add = |note_type: NoteType, compression: Compression, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "topic",
+            "sub",
+            "name",
+            note_type,
+            note,
+            NoteOptions {
+                compression: Some(compression),
+                ..NoteOptions::default()
+            },
+        )
+    };
+
+    // these come from the `zstd -19` cli, to show payloads from other encoders decode as well
+    let zstd_json =
+        "KLUv/WTRBp0BAKQCW3sidmFsaWRhdG9yIjoicGZjIiwiY29tbWlzc2lvbiI6IjAuMDUifSxdAQAhff2qDDajjrc=";
+    let zstd_not_json = "KLUv/SQIQQAAbm90IGpzb2547gT8";
+    let payloads = [
+        (
+            Compression::Gzip,
+            codec::encode(&Compression::Gzip, &json).unwrap(),
+            codec::encode(&Compression::Gzip, "not json").unwrap(),
+        ),
+        (Compression::Zstd, zstd_json.to_string(), zstd_not_json.to_string()),
+    ];
+    for (compression, payload, not_json) in payloads {
+        assert!(payload.len() < json.len() / 4);
+        add(NoteType::Json, compression.clone(), &payload).unwrap();
+        assert_eq!(codec::decode(&compression, &payload).unwrap(), json);
+
+        // the declared type is checked against the decompressed body
+        let err = add(NoteType::Json, compression, &not_json).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidJson(_)));
+    }
+    let payload = codec::encode(&Compression::Zstd, &json).unwrap();
+    add(NoteType::Json, Compression::Zstd, &payload).unwrap();
+    let err = add(NoteType::String, Compression::Gzip, "not gzip").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidCompression(_)));
+
+    // bodies over 16 times the size limit are refused however well they compress
+    let huge = "a".repeat(16 * 32 * 1024 + 1);
+    let payload = codec::encode(&Compression::Gzip, &huge).unwrap();
+    assert!(payload.len() < 32 * 1024);
+    let err = add(NoteType::String, Compression::Gzip, &payload).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidCompression(_)));
+
+    add(NoteType::Json, Compression::Zstd, zstd_json).unwrap();
+    let note = live_note(deps.as_ref(), &mock_env(), "topic", "sub", "name").unwrap();
+    assert_eq!(note.compression, Compression::Zstd);
+    assert_eq!(note.body().unwrap(), json);
+    assert_eq!(codec::encode(&Compression::None, &json).unwrap(), json);
+}
+
+#[test]
+fn note_hash() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let add = |deps: &mut OwnedDeps<_, _, _>, sub_topic: &str, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "chains",
+            sub_topic,
+            "denoms",
+            NoteType::String,
+            note,
+            NoteOptions::default(),
+        )
+        .unwrap()
+    };
+    let res = add(&mut deps, "phoenix-1", "uluna");
+    let hash = sha256_hex(b"uluna");
+    assert_eq!(hash, "e8e471bdc26f8d959eff6e89e4fa3e6f02ed7d3960360ffbc00246174aabb6f5");
+    assert_eq!(res.attributes[0].value, hash);
+    add(&mut deps, "pisco-1", "uluna");
+    add(&mut deps, "columbus-5", "uluna");
+    let note_hash = query_note_hash(deps.as_ref(), &env, "chains", "pisco-1", "denoms").unwrap();
+    assert_eq!(note_hash, hash);
+
+    let chains = |deps: &OwnedDeps<_, _, _>, hash: &str, start_after: Option<NoteKey>| {
+        query_notes_by_hash(deps.as_ref(), &env, hash, start_after, Some(2))
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|e| e.sub_topic)
+            .collect::<Vec<_>>()
+    };
+    // index order follows the length prefixed primary key, not the plain strings
+    assert_eq!(chains(&deps, &hash.to_uppercase(), None), vec!["pisco-1", "phoenix-1"]);
+    let start_after = NoteKey {
+        topic: "chains".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: "denoms".to_string(),
+    };
+    assert_eq!(chains(&deps, &hash, Some(start_after)), vec!["columbus-5"]);
+
+    // rewriting a note moves it to its new hash
+    add(&mut deps, "pisco-1", "uluna,uusd");
+    assert_eq!(chains(&deps, &hash, None), vec!["phoenix-1", "columbus-5"]);
+    assert_eq!(chains(&deps, &sha256_hex(b"uluna,uusd"), None), vec!["pisco-1"]);
+    exec_revert_note(deps.as_mut(), &env, &pie, "chains", "pisco-1", "denoms", 1).unwrap();
+    assert!(chains(&deps, &sha256_hex(b"uluna,uusd"), None).is_empty());
+    exec_rm_note(deps.as_mut(), &pie, "chains", "columbus-5", "denoms", None).unwrap();
+    assert_eq!(chains(&deps, &hash, None), vec!["pisco-1", "phoenix-1"]);
+}
+
+#[test]
+fn shared_note_bodies() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let add = |deps: &mut OwnedDeps<_, _, _>, sub_topic: &str, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "chains",
+            sub_topic,
+            "denoms",
+            NoteType::String,
+            note,
+            NoteOptions::default(),
+        )
+        .unwrap();
+    };
+    let refs = |deps: &OwnedDeps<_, _, _>, note: &str| {
+        NOTE_BODIES
+            .may_load(deps.as_ref().storage, &sha256_hex(note.as_bytes()))
+            .unwrap()
+            .map(|body| body.refs)
+    };
+    let bodies = |deps: &OwnedDeps<_, _, _>| {
+        NOTE_BODIES.keys_raw(deps.as_ref().storage, None, None, Order::Ascending).count()
+    };
+
+    add(&mut deps, "phoenix-1", "uluna");
+    add(&mut deps, "pisco-1", "uluna");
+    assert_eq!(bodies(&deps), 1);
+    assert_eq!(refs(&deps, "uluna"), Some(2));
+
+    // the replaced revision keeps its body alive from the history
+    add(&mut deps, "pisco-1", "uluna,uusd");
+    assert_eq!(refs(&deps, "uluna"), Some(2));
+    assert_eq!(refs(&deps, "uluna,uusd"), Some(1));
+    exec_revert_note(deps.as_mut(), &env, &pie, "chains", "pisco-1", "denoms", 1).unwrap();
+    assert_eq!(refs(&deps, "uluna"), Some(3));
+    let note = query_note_at_revision(deps.as_ref(), "chains", "pisco-1", "denoms", 2).unwrap();
+    assert_eq!(note.note, "uluna,uusd");
+
+    exec_rm_note(deps.as_mut(), &pie, "chains", "pisco-1", "denoms", None).unwrap();
+    assert_eq!(refs(&deps, "uluna"), Some(1));
+    assert_eq!(refs(&deps, "uluna,uusd"), None);
+
+    // without history a replaced body is released straight away
+    exec_update_config(deps.as_mut(), Some(0), None, None).unwrap();
+    add(&mut deps, "phoenix-1", "uluna,ukrw");
+    assert_eq!(refs(&deps, "uluna"), None);
+    exec_rm_sub_topic(deps.as_mut(), &pie, "chains", "phoenix-1", None).unwrap();
+    assert_eq!(bodies(&deps), 0);
+}
+
+#[test]
+fn note_links() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let key = |name: &str| NoteKey {
+        topic: "gov".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: name.to_string(),
+    };
+    let add = |deps: &mut OwnedDeps<_, _, _>, name: &str, links: Option<Vec<NoteKey>>| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "gov",
+            "phoenix-1",
+            name,
+            NoteType::String,
+            "body",
+            NoteOptions {
+                links,
+                ..NoteOptions::default()
+            },
+        )
+    };
+
+    add(&mut deps, "proposal-1", None).unwrap();
+    add(
+        &mut deps,
+        "proposals",
+        Some(vec![key("proposal-2"), key("proposal-1"), key("proposal-1")]),
+    )
+    .unwrap();
+    let note = live_note(deps.as_ref(), &env, "gov", "phoenix-1", "proposals").unwrap();
+    assert_eq!(note.links, vec![key("proposal-1"), key("proposal-2")]);
+    add(&mut deps, "summary", Some(vec![key("proposal-1")])).unwrap();
+
+    let backlinks =
+        query_backlinks(deps.as_ref(), "gov", "phoenix-1", "proposal-1", None, None).unwrap();
+    assert!(backlinks.exists);
+    assert_eq!(backlinks.entries, vec![key("proposals"), key("summary")]);
+    let backlinks = query_backlinks(
+        deps.as_ref(),
+        "gov",
+        "phoenix-1",
+        "proposal-1",
+        Some(key("proposals")),
+        None,
+    )
+    .unwrap();
+    assert_eq!(backlinks.entries, vec![key("summary")]);
+    let backlinks =
+        query_backlinks(deps.as_ref(), "gov", "phoenix-1", "proposal-2", None, None).unwrap();
+    assert!(!backlinks.exists);
+    assert_eq!(backlinks.entries, vec![key("proposals")]);
+    let dangling = query_dangling_links(deps.as_ref(), "gov", "phoenix-1", "proposals").unwrap();
+    assert_eq!(dangling, vec![key("proposal-2")]);
+
+    // a link to an alias reaches the note behind it
+    add(&mut deps, "proposal-3", None).unwrap();
+    exec_set_alias(deps.as_mut(), &env, &pie, "gov", "phoenix-1", "proposal-2", key("proposal-3"))
+        .unwrap();
+    let backlinks =
+        query_backlinks(deps.as_ref(), "gov", "phoenix-1", "proposal-2", None, None).unwrap();
+    assert!(backlinks.exists);
+    let dangling = query_dangling_links(deps.as_ref(), "gov", "phoenix-1", "proposals").unwrap();
+    assert!(dangling.is_empty());
+    exec_rm_note(deps.as_mut(), &pie, "gov", "phoenix-1", "proposal-3", None).unwrap();
+    let dangling = query_dangling_links(deps.as_ref(), "gov", "phoenix-1", "proposals").unwrap();
+    assert_eq!(dangling, vec![key("proposal-2")]);
+
+    // links are kept on rewrite unless replaced, and dropped with the note
+    add(&mut deps, "summary", None).unwrap();
+    let note = live_note(deps.as_ref(), &env, "gov", "phoenix-1", "summary").unwrap();
+    assert_eq!(note.links, vec![key("proposal-1")]);
+    add(&mut deps, "summary", Some(vec![])).unwrap();
+    exec_rm_note(deps.as_mut(), &pie, "gov", "phoenix-1", "proposals", None).unwrap();
+    let backlinks =
+        query_backlinks(deps.as_ref(), "gov", "phoenix-1", "proposal-1", None, None).unwrap();
+    assert!(backlinks.entries.is_empty());
+
+    let err =
+        add(&mut deps, "many", Some((0..21).map(|i| key(&i.to_string())).collect())).unwrap_err();
+    assert_eq!(err.to_string(), "A note can have at most 20 links, got 21");
+}
+
+#[test]
+fn topic_info() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let info = TopicInfo {
+        description: Some("validator metadata".to_string()),
+        owner_contact: Some("pfc-validator@protonmail.com".to_string()),
+        default_note_type: Some(NoteType::Json),
+    };
+    let err = exec_set_topic_info(deps.as_mut(), &pie, "validators", &info).unwrap_err();
+    assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "validators",
+        "phoenix-1",
+        "pfc",
+        NoteType::String,
+        "pfc",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    let err =
+        resolve_note_type(deps.as_ref().storage, "validators", "phoenix-1", None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "No note type given and validators_phoenix-1 has no default note type"
+    );
+
+    let err = exec_set_topic_info(deps.as_mut(), &Addr::unchecked("nobody"), "validators", &info)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+    let long = TopicInfo {
+        description: Some("x".repeat(513)),
+        ..TopicInfo::default()
+    };
+    let err = exec_set_topic_info(deps.as_mut(), &pie, "validators", &long).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid topic info: description is longer than 512 bytes");
+    exec_set_topic_info(deps.as_mut(), &pie, "validators", &info).unwrap();
+    let topics = query_topics(deps.as_ref(), None, None).unwrap();
+    assert_eq!(topics.entries[0].info, info);
+    assert_eq!(
+        resolve_note_type(deps.as_ref().storage, "validators", "phoenix-1", None).unwrap(),
+        NoteType::Json
+    );
+
+    // the subtopic default wins over the topic's, and a given type over both
+    let sub_info = TopicInfo {
+        default_note_type: Some(NoteType::Markdown),
+        ..TopicInfo::default()
+    };
+    exec_set_sub_topic_info(deps.as_mut(), &pie, "validators", "phoenix-1", &sub_info).unwrap();
+    let subs = query_sub_topics(deps.as_ref(), "validators", None, None).unwrap();
+    assert_eq!(subs.entries[0].info, sub_info);
+    assert_eq!(
+        resolve_note_type(deps.as_ref().storage, "validators", "phoenix-1", None).unwrap(),
+        NoteType::Markdown
+    );
+    assert_eq!(
+        resolve_note_type(deps.as_ref().storage, "validators", "phoenix-1", Some(NoteType::Url))
+            .unwrap(),
+        NoteType::Url
+    );
+
+    // removal takes the info with it
+    exec_rm_topic(deps.as_mut(), &pie, "validators", None).unwrap();
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "validators",
+        "phoenix-1",
+        "pfc",
+        NoteType::String,
+        "pfc",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_topics(deps.as_ref(), None, None).unwrap().entries[0].info,
+        TopicInfo::default()
+    );
+    let subs = query_sub_topics(deps.as_ref(), "validators", None, None).unwrap();
+    assert_eq!(subs.entries[0].info, TopicInfo::default());
+}
+
+#[test]
+fn patch_note() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let add = |deps: &mut OwnedDeps<_, _, _>, note_type: NoteType, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "validators",
+            "phoenix-1",
+            "pfc",
+            note_type,
+            note,
+            NoteOptions {
+                compression: Some(Compression::Gzip),
+                ..NoteOptions::default()
+            },
+        )
+        .unwrap();
+    };
+    let patch = |deps: &mut OwnedDeps<_, _, _>, patch: NotePatch| {
+        exec_patch_note(deps.as_mut(), &env, &pie, "validators", "phoenix-1", "pfc", &patch, None)
+    };
+    let body = |deps: &OwnedDeps<_, _, _>| {
+        live_note(deps.as_ref(), &env, "validators", "phoenix-1", "pfc").unwrap().body().unwrap()
+    };
+
+    let doc = r#"{"title":"Goodbye!","author":{"givenName":"John","familyName":"Doe"},"tags":["example","sample"],"content":"This will be unchanged"}"#;
+    add(&mut deps, NoteType::Json, &codec::encode(&Compression::Gzip, doc).unwrap());
+
+    // the example from RFC 7396
+    let merge = r#"{"title":"Hello!","phoneNumber":"+01-123-456-7890","author":{"familyName":null},"tags":["example"]}"#;
+    let res = patch(&mut deps, NotePatch::Merge(merge.to_string())).unwrap();
+    let expected = r#"{"author":{"givenName":"John"},"content":"This will be unchanged","phoneNumber":"+01-123-456-7890","tags":["example"],"title":"Hello!"}"#;
+    assert_eq!(body(&deps), expected);
+    // the patched body is no longer compressed
+    let note = live_note(deps.as_ref(), &env, "validators", "phoenix-1", "pfc").unwrap();
+    assert_eq!(note.compression, Compression::None);
+    assert_eq!(note.revision, 2);
+    assert_eq!(res.attributes[0].value, note.hash);
+
+    let ops = r#"[
+        {"op":"test","path":"/title","value":"Hello!"},
+        {"op":"add","path":"/tags/-","value":"sample"},
+        {"op":"add","path":"/tags/0","value":"first"},
+        {"op":"remove","path":"/phoneNumber"},
+        {"op":"replace","path":"/author/givenName","value":"Jane"},
+        {"op":"copy","from":"/author","path":"/editor"},
+        {"op":"move","from":"/content","path":"/a~1b"}
+    ]"#;
+    patch(&mut deps, NotePatch::Json(ops.to_string())).unwrap();
+    let expected = r#"{"a/b":"This will be unchanged","author":{"givenName":"Jane"},"editor":{"givenName":"Jane"},"tags":["first","example","sample"],"title":"Hello!"}"#;
+    assert_eq!(body(&deps), expected);
+
+    // a failing operation leaves the note alone
+    let ops = r#"[{"op":"remove","path":"/title"},{"op":"test","path":"/title","value":"Hello!"}]"#;
+    let err = patch(&mut deps, NotePatch::Json(ops.to_string())).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid patch: /title does not exist");
+    let ops = r#"[{"op":"remove","path":"/tags/01"}]"#;
+    let err = patch(&mut deps, NotePatch::Json(ops.to_string())).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid patch: invalid array index \"01\"");
+    let err = patch(&mut deps, NotePatch::Json(r#"[{"op":"nope"}]"#.to_string())).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidPatch(_)));
+    assert_eq!(body(&deps), expected);
+
+    let err = exec_patch_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "validators",
+        "phoenix-1",
+        "pfc",
+        &NotePatch::Merge("{}".to_string()),
+        Some(2),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::RevisionMismatch { .. }));
+
+    add(&mut deps, NoteType::String, &codec::encode(&Compression::Gzip, "text").unwrap());
+    let err = patch(&mut deps, NotePatch::Merge("{}".to_string())).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid patch: note is String, not Json");
+
+    // numbers are written back as they were given
+    let doc = r#"{"rate":0.050,"max":1e400,"name":"caf\u00e9\n"}"#;
+    add(&mut deps, NoteType::Json, &codec::encode(&Compression::Gzip, doc).unwrap());
+    patch(&mut deps, NotePatch::Merge(r#"{"min":-2.5}"#.to_string())).unwrap();
+    assert_eq!(body(&deps), r#"{"max":1e400,"min":-2.5,"name":"café\n","rate":0.050}"#);
+    // but compare by value
+    let ops = r#"[
+        {"op":"test","path":"/rate","value":5e-2},
+        {"op":"test","path":"/max","value":10.0E+399},
+        {"op":"test","path":"/min","value":-25e-1}
+    ]"#;
+    patch(&mut deps, NotePatch::Json(ops.to_string())).unwrap();
+    let ops = r#"[{"op":"test","path":"/rate","value":-0.05}]"#;
+    let err = patch(&mut deps, NotePatch::Json(ops.to_string())).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid patch: test failed at /rate");
+    let ops =
+        r#"[{"op":"add","path":"/zero","value":-0.0},{"op":"test","path":"/zero","value":0}]"#;
+    patch(&mut deps, NotePatch::Json(ops.to_string())).unwrap();
+
+    // copies can double the document with each operation, so the size is checked as they apply
+    add(
+        &mut deps,
+        NoteType::Json,
+        &codec::encode(&Compression::Gzip, r#"{"a":"0123456789"}"#).unwrap(),
+    );
+    let ops = format!(
+        "[{}]",
+        (0..20)
+            .map(|i| format!(r#"{{"op":"copy","from":"","path":"/{}"}}"#, i))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    let err = patch(&mut deps, NotePatch::Json(ops)).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ContractError::NoteTooLarge {
+                limit: 32768,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+    assert_eq!(body(&deps), r#"{"a":"0123456789"}"#);
+    let ops = r#"[{"op":"copy","from":"/a","path":"/b"},{"op":"remove","path":"/a"}]"#;
+    patch(&mut deps, NotePatch::Json(ops.to_string())).unwrap();
+    assert_eq!(body(&deps), r#"{"b":"0123456789"}"#);
+
+    // an expiry that has passed stays as it is, waiting for PruneExpired
+    let expires_at = env.block.time.plus_seconds(60);
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "validators",
+        "phoenix-1",
+        "pfc",
+        NoteType::Json,
+        "{}",
+        NoteOptions {
+            expires_at: Some(expires_at),
+            ..NoteOptions::default()
+        },
+    )
+    .unwrap();
+    let mut later = mock_env();
+    later.block.time = expires_at.plus_seconds(1);
+    exec_patch_note(
+        deps.as_mut(),
+        &later,
+        &pie,
+        "validators",
+        "phoenix-1",
+        "pfc",
+        &NotePatch::Merge(r#"{"a":1}"#.to_string()),
+        None,
+    )
+    .unwrap();
+    let note = live_note(deps.as_ref(), &env, "validators", "phoenix-1", "pfc").unwrap();
+    assert_eq!(note.expires_at, Some(expires_at));
+    assert_eq!(note.body().unwrap(), r#"{"a":1}"#);
+}
+
+#[test]
+fn list_notes() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let key = NoteKey {
+        topic: "events".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: "recent".to_string(),
+    };
+    let append = |deps: &mut OwnedDeps<_, _, _>, item: &str, capacity: Option<u32>| {
+        exec_append_to_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "events",
+            "phoenix-1",
+            "recent",
+            Some(NoteType::Json),
+            item,
+            capacity,
+        )
+    };
+    let items = |deps: &OwnedDeps<_, _, _>, start_after: Option<u64>, order: ListOrder| {
+        query_list_items(deps.as_ref(), &env, &key, start_after, Some(2), Some(order))
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|i| (i.index, i.item))
+            .collect::<Vec<_>>()
+    };
+
+    for n in 0..4 {
+        let res = append(&mut deps, &format!("{{\"height\":{}}}", n), None).unwrap();
+        assert_eq!(res.attributes[0].value, n.to_string());
+    }
+    let note = live_note(deps.as_ref(), &env, "events", "phoenix-1", "recent").unwrap();
+    assert_eq!(
+        note.kind,
+        NoteKind::List {
+            capacity: None
+        }
+    );
+    assert_eq!(note.revision, 4);
+    assert_eq!(
+        items(&deps, None, ListOrder::Ascending),
+        vec![(0, r#"{"height":0}"#.to_string()), (1, r#"{"height":1}"#.to_string())]
+    );
+    assert_eq!(items(&deps, Some(1), ListOrder::Ascending)[0].0, 2);
+    assert_eq!(items(&deps, None, ListOrder::Descending)[0].0, 3);
+    assert_eq!(items(&deps, Some(3), ListOrder::Descending)[0].0, 2);
+
+    let err = append(&mut deps, "not json", None).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidJson(_)));
+    let err = append(&mut deps, "{}", Some(0)).unwrap_err();
+    assert_eq!(err.to_string(), "A list holds between 1 and 1000 items, got a capacity of 0");
+
+    // a capacity drops the oldest items
+    append(&mut deps, "{}", Some(3)).unwrap();
+    let mut later = env.clone();
+    later.block.height += 1;
+    exec_append_to_note(
+        deps.as_mut(),
+        &later,
+        &pie,
+        "events",
+        "phoenix-1",
+        "recent",
+        None,
+        "{}",
+        None,
+    )
+    .unwrap();
+    let all = query_list_items(deps.as_ref(), &env, &key, None, None, None).unwrap();
+    assert_eq!(all.entries.iter().map(|i| i.index).collect::<Vec<_>>(), vec![3, 4, 5]);
+    let note = live_note(deps.as_ref(), &env, "events", "phoenix-1", "recent").unwrap();
+    assert_eq!(note.revision, 6);
+    assert_eq!(note.updated_at, NoteStamp::from(&later.block));
+    assert_eq!(note.created_at, NoteStamp::from(&env.block));
+
+    // lists are only written by appending, and go with the note
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "events",
+        "phoenix-1",
+        "recent",
+        NoteType::Json,
+        "{}",
+        NoteOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), r#"Note "events"/"phoenix-1"/"recent" is not a plain note"#);
+    exec_rm_note(deps.as_mut(), &pie, "events", "phoenix-1", "recent", None).unwrap();
+    append(&mut deps, "{}", None).unwrap();
+    let all = query_list_items(deps.as_ref(), &env, &key, None, None, None).unwrap();
+    assert_eq!(all.entries.iter().map(|i| i.index).collect::<Vec<_>>(), vec![0]);
+
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "events",
+        "phoenix-1",
+        "plain",
+        NoteType::Json,
+        "{}",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    let err = exec_append_to_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "events",
+        "phoenix-1",
+        "plain",
+        None,
+        "{}",
+        None,
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), r#"Note "events"/"phoenix-1"/"plain" is not a list note"#);
+
+    // items add up to at most 1MiB, which a capped list keeps to by dropping its oldest
+    let item = "a".repeat(32 * 1024);
+    let append_big = |deps: &mut OwnedDeps<_, _, _>, capacity: Option<u32>| {
+        exec_append_to_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "events",
+            "phoenix-1",
+            "big",
+            Some(NoteType::String),
+            &item,
+            capacity,
+        )
+    };
+    for _ in 0..32 {
+        append_big(&mut deps, None).unwrap();
+    }
+    let err = append_big(&mut deps, None).unwrap_err();
+    assert_eq!(err.to_string(), "Note is too large: 1081344 bytes, the limit is 1048576 bytes");
+    let res = append_big(&mut deps, Some(100)).unwrap();
+    assert_eq!(res.attributes[0].value, "32");
+    let key = NoteKey::from(("events", "phoenix-1", "big"));
+    let first = query_list_items(deps.as_ref(), &env, &key, None, Some(1), None).unwrap();
+    assert_eq!(first.entries[0].index, 1);
+}
+
+#[test]
+fn counter_notes() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let value = |deps: &OwnedDeps<_, _, _>| {
+        live_note(deps.as_ref(), &env, "stats", "phoenix-1", "blocks").unwrap().kind
+    };
+
+    let res =
+        exec_increment_note(deps.as_mut(), &env, &pie, "stats", "phoenix-1", "blocks", 5).unwrap();
+    assert_eq!(res.attributes[0].value, "5");
+    exec_decrement_note(deps.as_mut(), &env, &pie, "stats", "phoenix-1", "blocks", 2).unwrap();
+    assert_eq!(
+        value(&deps),
+        NoteKind::Counter {
+            value: 3
+        }
+    );
+
+    let err = exec_decrement_note(deps.as_mut(), &env, &pie, "stats", "phoenix-1", "blocks", 4)
+        .unwrap_err();
+    assert_eq!(err.to_string(), r#"Counter "stats"/"phoenix-1"/"blocks" would go below 0"#);
+    exec_increment_note(deps.as_mut(), &env, &pie, "stats", "phoenix-1", "blocks", u64::MAX - 3)
+        .unwrap();
+    let err = exec_increment_note(deps.as_mut(), &env, &pie, "stats", "phoenix-1", "blocks", 1)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::CounterOverflow(_)));
+    assert_eq!(
+        value(&deps),
+        NoteKind::Counter {
+            value: u64::MAX
+        }
+    );
+    // each update is a revision, failed ones aside
+    let note = live_note(deps.as_ref(), &env, "stats", "phoenix-1", "blocks").unwrap();
+    assert_eq!(note.revision, 3);
+    let err =
+        exec_rm_note(deps.as_mut(), &pie, "stats", "phoenix-1", "blocks", Some(2)).unwrap_err();
+    assert!(matches!(err, ContractError::RevisionMismatch { .. }));
+
+    let bob = Addr::unchecked("bob");
+    exec_increment_note(deps.as_mut(), &env, &bob, "stats", "phoenix-1", "blocks", 1).unwrap_err();
+    exec_append_to_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "stats",
+        "phoenix-1",
+        "log",
+        Some(NoteType::String),
+        "x",
+        None,
+    )
+    .unwrap();
+    let err =
+        exec_increment_note(deps.as_mut(), &env, &pie, "stats", "phoenix-1", "log", 1).unwrap_err();
+    assert_eq!(err.to_string(), r#"Note "stats"/"phoenix-1"/"log" is not a counter note"#);
+}
+
+#[test]
+fn map_notes() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let key = NoteKey {
+        topic: "rpc".to_string(),
+        sub_topic: "mainnet".to_string(),
+        name: "endpoints".to_string(),
+    };
+    let set = |deps: &mut OwnedDeps<_, _, _>, field: &str, value: &str| {
+        exec_set_note_field(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "rpc",
+            "mainnet",
+            "endpoints",
+            Some(NoteType::Url),
+            field,
+            value,
+        )
+    };
+    let fields = |deps: &OwnedDeps<_, _, _>, start_after: Option<&str>| {
+        query_note_fields(deps.as_ref(), &env, &key, start_after.map(str::to_string), Some(2))
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|f| (f.field, f.value))
+            .collect::<Vec<_>>()
+    };
+
+    set(&mut deps, "phoenix-1", "https://rpc.terra.dev").unwrap();
+    set(&mut deps, "columbus-5", "https://rpc.classic.dev").unwrap();
+    set(&mut deps, "pisco-1", "https://rpc.pisco.dev").unwrap();
+    // replacing a field leaves the count alone
+    set(&mut deps, "phoenix-1", "https://rpc2.terra.dev").unwrap();
+    let note = live_note(deps.as_ref(), &env, "rpc", "mainnet", "endpoints").unwrap();
+    assert_eq!(
+        note.kind,
+        NoteKind::Map {
+            fields: 3,
+            size: 92,
+        }
+    );
+    assert_eq!(note.revision, 4);
+    assert_eq!(
+        fields(&deps, None),
+        vec![
+            ("columbus-5".to_string(), "https://rpc.classic.dev".to_string()),
+            ("phoenix-1".to_string(), "https://rpc2.terra.dev".to_string())
+        ]
+    );
+    assert_eq!(fields(&deps, Some("phoenix-1"))[0].0, "pisco-1");
+
+    let err = set(&mut deps, "pisco-1", "not a url").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidUrl(_)));
+    let err = set(&mut deps, "", "https://rpc.terra.dev").unwrap_err();
+    assert!(matches!(err, ContractError::InvalidFieldName(_)));
+
+    let mut later = env.clone();
+    later.block.height += 1;
+    let remove = |deps: &mut OwnedDeps<_, _, _>, field: &str| {
+        exec_remove_note_field(deps.as_mut(), &later, &pie, "rpc", "mainnet", "endpoints", field)
+    };
+    remove(&mut deps, "columbus-5").unwrap();
+    let err = remove(&mut deps, "columbus-5").unwrap_err();
+    assert_eq!(err.to_string(), r#"Map "rpc"/"mainnet"/"endpoints" has no field "columbus-5""#);
+    assert_eq!(fields(&deps, None)[0].0, "phoenix-1");
+    let note = live_note(deps.as_ref(), &env, "rpc", "mainnet", "endpoints").unwrap();
+    assert_eq!(
+        note.kind,
+        NoteKind::Map {
+            fields: 2,
+            size: 59,
+        }
+    );
+    assert_eq!(note.revision, 5);
+    assert_eq!(note.updated_at, NoteStamp::from(&later.block));
+
+    // fields go with the note
+    exec_rm_note(deps.as_mut(), &pie, "rpc", "mainnet", "endpoints", None).unwrap();
+    set(&mut deps, "pisco-1", "https://rpc.pisco.dev").unwrap();
+    assert_eq!(fields(&deps, None).len(), 1);
+
+    exec_increment_note(deps.as_mut(), &env, &pie, "rpc", "mainnet", "hits", 1).unwrap();
+    let err =
+        exec_set_note_field(deps.as_mut(), &env, &pie, "rpc", "mainnet", "hits", None, "a", "b")
+            .unwrap_err();
+    assert_eq!(err.to_string(), r#"Note "rpc"/"mainnet"/"hits" is not a map note"#);
+
+    // field names and values add up to at most 1MiB
+    let value = "a".repeat(32 * 1024);
+    let set_blob = |deps: &mut OwnedDeps<_, _, _>, field: &str, value: &str| {
+        exec_set_note_field(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "rpc",
+            "mainnet",
+            "blobs",
+            Some(NoteType::String),
+            field,
+            value,
+        )
+    };
+    for i in 0..31 {
+        set_blob(&mut deps, &format!("f{:02}", i), &value).unwrap();
+    }
+    let err = set_blob(&mut deps, "f31", &value).unwrap_err();
+    assert_eq!(err.to_string(), "Note is too large: 1048672 bytes, the limit is 1048576 bytes");
+    set_blob(&mut deps, "f00", "a").unwrap();
+    set_blob(&mut deps, "f31", &value).unwrap();
+}
+
+#[test]
+fn note_aliases() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let key = |name: &str| NoteKey {
+        topic: "station".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: name.to_string(),
+    };
+    let alias = |deps: &mut OwnedDeps<_, _, _>, name: &str, target: &str| {
+        exec_set_alias(deps.as_mut(), &env, &pie, "station", "phoenix-1", name, key(target))
+    };
+    for name in ["v1", "v2"] {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "station",
+            "phoenix-1",
+            name,
+            NoteType::String,
+            name,
+            NoteOptions::default(),
+        )
+        .unwrap();
+    }
+
+    alias(&mut deps, "latest", "v1").unwrap();
+    alias(&mut deps, "stable", "latest").unwrap();
+    let note = live_note(deps.as_ref(), &env, "station", "phoenix-1", "stable").unwrap();
+    assert_eq!((note.name.as_str(), note.note.as_str()), ("v1", "v1"));
+    // repointing an alias moves everything that goes through it
+    alias(&mut deps, "latest", "v2").unwrap();
+    let note = live_note(deps.as_ref(), &env, "station", "phoenix-1", "stable").unwrap();
+    assert_eq!(note.name, "v2");
+    let raw = query_alias(deps.as_ref(), "station", "phoenix-1", "stable").unwrap();
+    assert_eq!(raw.target, key("latest"));
+
+    let err = alias(&mut deps, "latest", "stable").unwrap_err();
+    assert_eq!(err.to_string(), r#"Alias loop at "station"/"phoenix-1"/"latest""#);
+    alias(&mut deps, "missing", "nothing").unwrap_err();
+    let err = alias(&mut deps, "v1", "v2").unwrap_err();
+    assert_eq!(err.to_string(), r#"Name "station"/"phoenix-1"/"v1" is already in use"#);
+    let err = exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "phoenix-1",
+        "latest",
+        NoteType::String,
+        "x",
+        NoteOptions::default(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NameTaken(_)));
+
+    // a chain is only followed so far
+    let mut target = "v2".to_string();
+    for n in 0..8 {
+        let name = format!("hop{}", n);
+        alias(&mut deps, &name, &target).unwrap();
+        target = name;
+    }
+    let err = alias(&mut deps, "hop8", &target).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"Alias "station"/"phoenix-1"/"hop8" is more than 8 aliases from a note"#
+    );
+
+    // so is one lengthened from the far end, counting the aliases behind it
+    alias(&mut deps, "far", "v2").unwrap();
+    let err = alias(&mut deps, "hop0", "far").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"Alias "station"/"phoenix-1"/"hop7" is more than 8 aliases from a note"#
+    );
+    live_note(deps.as_ref(), &env, "station", "phoenix-1", "hop7").unwrap();
+    alias(&mut deps, "hop1", "far").unwrap();
+    live_note(deps.as_ref(), &env, "station", "phoenix-1", "hop7").unwrap();
+    // a new alias under a name others already point at is checked the same way
+    exec_remove_alias(deps.as_mut(), &pie, "station", "phoenix-1", "far").unwrap();
+    let err = alias(&mut deps, "far", "hop0").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"Alias "station"/"phoenix-1"/"hop7" is more than 8 aliases from a note"#
+    );
+    let err = alias(&mut deps, "far", "hop2").unwrap_err();
+    assert_eq!(err.to_string(), r#"Alias loop at "station"/"phoenix-1"/"far""#);
+    alias(&mut deps, "far", "v2").unwrap();
+
+    exec_remove_alias(deps.as_mut(), &pie, "station", "phoenix-1", "stable").unwrap();
+    live_note(deps.as_ref(), &env, "station", "phoenix-1", "stable").unwrap_err();
+    let res = exec_rm_sub_topic(deps.as_mut(), &pie, "station", "phoenix-1", None).unwrap();
+    assert_eq!(res.attributes[2].value, "8");
+    let res = exec_rm_sub_topic(deps.as_mut(), &pie, "station", "phoenix-1", None).unwrap();
+    assert_eq!(res.attributes[2].value, "2");
+    query_alias(deps.as_ref(), "station", "phoenix-1", "latest").unwrap_err();
+}
+
+#[test]
+fn move_and_rename() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let add = |deps: &mut OwnedDeps<_, _, _>, sub_topic: &str, name: &str, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "chains",
+            sub_topic,
+            name,
+            NoteType::String,
+            note,
+            NoteOptions::default(),
+        )
+        .unwrap();
+    };
+    add(&mut deps, "terra", "rpc", "first");
+    add(&mut deps, "terra", "rpc", "second");
+    exec_append_to_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "chains",
+        "terra",
+        "log",
+        Some(NoteType::String),
+        "item",
+        None,
+    )
+    .unwrap();
+
+    let rpc = NoteKey {
+        topic: "chains".to_string(),
+        sub_topic: "terra".to_string(),
+        name: "rpc".to_string(),
+    };
+    exec_set_alias(deps.as_mut(), &env, &pie, "docs", "pointers", "rpc", rpc.clone()).unwrap();
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "docs",
+        "pointers",
+        "page",
+        NoteType::String,
+        "see rpc",
+        NoteOptions {
+            links: Some(vec![rpc]),
+            ..NoteOptions::default()
+        },
+    )
+    .unwrap();
+
+    let to = NoteKey {
+        topic: "chains".to_string(),
+        sub_topic: "terra".to_string(),
+        name: "lcd".to_string(),
+    };
+    exec_move_note(deps.as_mut(), &pie, "chains", "terra", "rpc", to.clone()).unwrap();
+    // aliases and links follow the note
+    let alias = query_alias(deps.as_ref(), "docs", "pointers", "rpc").unwrap();
+    assert_eq!(alias.target, to);
+    let page = live_note(deps.as_ref(), &env, "docs", "pointers", "page").unwrap();
+    assert_eq!(page.links, vec![to.clone()]);
+    let backlinks = query_backlinks(deps.as_ref(), "chains", "terra", "lcd", None, None).unwrap();
+    assert_eq!(backlinks.entries.len(), 1);
+    live_note(deps.as_ref(), &env, "chains", "terra", "rpc").unwrap_err();
+    let note = live_note(deps.as_ref(), &env, "chains", "terra", "lcd").unwrap();
+    assert_eq!((note.revision, note.note.as_str(), note.writer), (2, "second", pie.clone()));
+    let history =
+        query_note_history(deps.as_ref(), "chains", "terra", "lcd", None, None).unwrap().entries;
+    assert_eq!(history[0].note, "first");
+    assert_eq!(history[0].name, "lcd");
+    let err = exec_move_note(deps.as_mut(), &pie, "chains", "terra", "log", to).unwrap_err();
+    assert_eq!(err.to_string(), r#"Name "chains"/"terra"/"lcd" is already in use"#);
+
+    // subtopics move in batches, trash included, then go
+    add(&mut deps, "terra", "fcd", "x");
+    add(&mut deps, "terra", "old", "old v1");
+    add(&mut deps, "terra", "old", "old v2");
+    exec_trash_note(deps.as_mut(), &env, &pie, "chains", "terra", "old", None).unwrap();
+    exec_set_sub_topic_info(
+        deps.as_mut(),
+        &pie,
+        "chains",
+        "terra",
+        &TopicInfo {
+            description: Some("terra".to_string()),
+            ..TopicInfo::default()
+        },
+    )
+    .unwrap();
+    let res = exec_rename_sub_topic(deps.as_mut(), &pie, "chains", "terra", "phoenix-1", Some(2))
+        .unwrap();
+    assert_eq!(res.attributes[1].value, "false");
+    let res = exec_rename_sub_topic(deps.as_mut(), &pie, "chains", "terra", "phoenix-1", Some(2))
+        .unwrap();
+    assert_eq!(res.attributes[1].value, "true");
+    let subs = query_sub_topics(deps.as_ref(), "chains", None, None).unwrap().entries;
+    assert_eq!(subs.len(), 1);
+    assert_eq!(subs[0].sub_topic, "phoenix-1");
+    assert_eq!(subs[0].info.description.as_deref(), Some("terra"));
+    let key = NoteKey {
+        topic: "chains".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: "log".to_string(),
+    };
+    let items = query_list_items(deps.as_ref(), &env, &key, None, None, None).unwrap();
+    assert_eq!(items.entries[0].item, "item");
+    let trashed = query_trash(deps.as_ref(), None, None).unwrap().entries;
+    assert_eq!(
+        (trashed[0].note.sub_topic.as_str(), trashed[0].note.name.as_str()),
+        ("phoenix-1", "old")
+    );
+    exec_restore_note(deps.as_mut(), &pie, "chains", "phoenix-1", "old").unwrap();
+    let history = query_note_history(deps.as_ref(), "chains", "phoenix-1", "old", None, None)
+        .unwrap()
+        .entries;
+    assert_eq!((history[0].note.as_str(), history[0].sub_topic.as_str()), ("old v1", "phoenix-1"));
+
+    let res = exec_rename_topic(deps.as_mut(), &pie, "chains", "networks", None).unwrap();
+    assert_eq!(res.attributes[2].value, "true");
+    let topics = query_topics(deps.as_ref(), None, None).unwrap().entries;
+    assert_eq!(
+        topics.iter().map(|t| t.topic.as_str()).collect::<Vec<_>>(),
+        vec!["docs", "networks"]
+    );
+    live_note(deps.as_ref(), &env, "networks", "phoenix-1", "lcd").unwrap();
+    let alias = query_alias(deps.as_ref(), "docs", "pointers", "rpc").unwrap();
+    assert_eq!(alias.target.to_string(), r#""networks"/"phoenix-1"/"lcd""#);
+    let err = exec_rename_topic(deps.as_mut(), &pie, "networks", "networks", None).unwrap_err();
+    assert!(matches!(err, ContractError::MoveToSelf(_)));
+
+    // batches are cut short by the entries they rewrite, not just the notes they move
+    for i in 0..1000 {
+        exec_append_to_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "networks",
+            "phoenix-1",
+            "a-log",
+            Some(NoteType::String),
+            &i.to_string(),
+            None,
+        )
+        .unwrap();
+    }
+    let res = exec_rename_sub_topic(deps.as_mut(), &pie, "networks", "phoenix-1", "columbus", None)
+        .unwrap();
+    assert_eq!(
+        (res.attributes[0].value.as_str(), res.attributes[1].value.as_str()),
+        ("1", "false")
+    );
+    let res = exec_rename_sub_topic(deps.as_mut(), &pie, "networks", "phoenix-1", "columbus", None)
+        .unwrap();
+    assert_eq!(res.attributes[1].value, "true");
+    let key = NoteKey {
+        topic: "networks".to_string(),
+        sub_topic: "columbus".to_string(),
+        name: "a-log".to_string(),
+    };
+    let items = query_list_items(deps.as_ref(), &env, &key, None, None, None).unwrap();
+    assert_eq!(items.entries[0].item, "0");
+}
+
+#[test]
+fn copy_sub_topic() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let bob = Addr::unchecked("bob");
+    exec_add_writer(deps.as_mut(), "bob", "bob").unwrap();
+    let add = |deps: &mut OwnedDeps<_, _, _>, sub_topic: &str, name: &str, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "station",
+            sub_topic,
+            name,
+            NoteType::String,
+            note,
+            NoteOptions::default(),
+        )
+        .unwrap();
+    };
+    add(&mut deps, "phoenix-1", "fcd", "fcd v1");
+    add(&mut deps, "phoenix-1", "fcd", "fcd v2");
+    add(&mut deps, "phoenix-1", "lcd", "lcd");
+    add(&mut deps, "phoenix-1", "rpc", "rpc");
+    exec_set_note_field(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "phoenix-1",
+        "peers",
+        Some(NoteType::String),
+        "a",
+        "peer a",
+    )
+    .unwrap();
+    add(&mut deps, "pisco-1", "lcd", "pisco lcd");
+
+    let copy = |deps: &mut OwnedDeps<_, _, _>, overwrite: bool, start_after: Option<String>| {
+        let res = exec_copy_sub_topic(
+            deps.as_mut(),
+            &env,
+            &bob,
+            "station",
+            "phoenix-1",
+            "pisco-1",
+            overwrite,
+            start_after,
+            Some(2),
+        )
+        .unwrap();
+        res.attributes.into_iter().map(|a| a.value).collect::<Vec<_>>()
+    };
+    assert_eq!(copy(&mut deps, false, None), vec!["1", "1", "false", "lcd", "0"]);
+    assert_eq!(copy(&mut deps, false, Some("lcd".to_string())), vec!["2", "0", "true", "rpc", "0"]);
+
+    let note = live_note(deps.as_ref(), &env, "station", "pisco-1", "fcd").unwrap();
+    assert_eq!((note.note.as_str(), note.revision, note.writer), ("fcd v2", 1, bob.clone()));
+    assert!(query_note_history(deps.as_ref(), "station", "pisco-1", "fcd", None, None)
+        .unwrap()
+        .entries
+        .is_empty());
+    let note = live_note(deps.as_ref(), &env, "station", "pisco-1", "lcd").unwrap();
+    assert_eq!(note.note, "pisco lcd");
+    let key = NoteKey {
+        topic: "station".to_string(),
+        sub_topic: "pisco-1".to_string(),
+        name: "peers".to_string(),
+    };
+    let fields = query_note_fields(deps.as_ref(), &env, &key, None, None).unwrap();
+    assert_eq!(fields.entries[0].value, "peer a");
+
+    // copies share bodies with the notes they came from
+    let hash = sha256_hex(b"rpc");
+    assert_eq!(NOTE_BODIES.load(&deps.storage, &hash).unwrap().refs, 2);
+    exec_rm_note(deps.as_mut(), &pie, "station", "phoenix-1", "rpc", None).unwrap();
+    let note = live_note(deps.as_ref(), &env, "station", "pisco-1", "rpc").unwrap();
+    assert_eq!(note.note, "rpc");
+
+    // a note that is overwritten goes with its draft
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "pisco-1",
+        "lcd",
+        NoteType::String,
+        "pisco lcd draft",
+        NoteOptions {
+            draft: true,
+            ..NoteOptions::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(copy(&mut deps, true, None), vec!["2", "0", "false", "lcd", "1"]);
+    assert!(query_drafts(deps.as_ref(), None, None).unwrap().entries.is_empty());
+    let note = live_note(deps.as_ref(), &env, "station", "pisco-1", "lcd").unwrap();
+    assert_eq!(note.note, "lcd");
+
+    // a batch ends once it has written enough entries, whatever its limit
+    for i in 0..1000 {
+        exec_append_to_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "station",
+            "phoenix-1",
+            "a-log",
+            Some(NoteType::String),
+            &i.to_string(),
+            None,
+        )
+        .unwrap();
+    }
+    assert_eq!(copy(&mut deps, false, None), vec!["1", "0", "false", "a-log", "0"]);
+    let key = NoteKey {
+        topic: "station".to_string(),
+        sub_topic: "pisco-1".to_string(),
+        name: "a-log".to_string(),
+    };
+    let items = query_list_items(deps.as_ref(), &env, &key, None, None, None).unwrap();
+    assert_eq!(items.entries[0].item, "0");
+}
+
+#[test]
+fn soft_delete() {
+    let mut deps = setup();
+    let mut env = mock_env();
+    let pie = Addr::unchecked("pie");
+    exec_update_config(deps.as_mut(), None, None, Some(60)).unwrap();
+    let add = |deps: &mut OwnedDeps<_, _, _>, env: &cosmwasm_std::Env, note: &str| {
+        exec_add_note(
+            deps.as_mut(),
+            env,
+            &pie,
+            "station",
+            "phoenix-1",
+            "fcd",
+            NoteType::String,
+            note,
+            NoteOptions {
+                tags: Some(vec!["infra".to_string()]),
+                ..NoteOptions::default()
+            },
+        )
+    };
+    add(&mut deps, &env, "v1").unwrap();
+    add(&mut deps, &env, "v2").unwrap();
+
+    let err = exec_trash_note(deps.as_mut(), &env, &pie, "station", "phoenix-1", "fcd", Some(1))
+        .unwrap_err();
+    assert!(matches!(err, ContractError::RevisionMismatch { .. }));
+    exec_trash_note(deps.as_mut(), &env, &pie, "station", "phoenix-1", "fcd", Some(2)).unwrap();
+    live_note(deps.as_ref(), &env, "station", "phoenix-1", "fcd").unwrap_err();
+    assert!(query_notes_by_tag(deps.as_ref(), &env, "infra", None, None)
+        .unwrap()
+        .entries
+        .is_empty());
+    let trashed = query_trash(deps.as_ref(), None, None).unwrap().entries;
+    assert_eq!(trashed.len(), 1);
+    assert_eq!((trashed[0].note.note.as_str(), &trashed[0].removed_by), ("v2", &pie));
+    assert_eq!(trashed[0].purge_after, env.block.time.plus_seconds(60));
+    query_note_history(deps.as_ref(), "station", "phoenix-1", "fcd", None, None).unwrap_err();
+    let err = query_note_at_revision(deps.as_ref(), "station", "phoenix-1", "fcd", 1).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"Generic error: Note is in the trash: "station"/"phoenix-1"/"fcd""#
+    );
+
+    // restoring brings back the note as it was, history included
+    exec_restore_note(deps.as_mut(), &pie, "station", "phoenix-1", "fcd").unwrap();
+    let note = live_note(deps.as_ref(), &env, "station", "phoenix-1", "fcd").unwrap();
+    assert_eq!((note.note.as_str(), note.revision), ("v2", 2));
+    assert_eq!(
+        query_note_history(deps.as_ref(), "station", "phoenix-1", "fcd", None, None)
+            .unwrap()
+            .entries
+            .len(),
+        1
+    );
+    assert_eq!(
+        query_notes_by_tag(deps.as_ref(), &env, "infra", None, None).unwrap().entries.len(),
+        1
+    );
+    let err = exec_restore_note(deps.as_mut(), &pie, "station", "phoenix-1", "fcd").unwrap_err();
+    assert_eq!(err.to_string(), r#"Note "station"/"phoenix-1"/"fcd" is not in the trash"#);
+
+    // nothing is purged before the retention period is up
+    exec_trash_note(deps.as_mut(), &env, &pie, "station", "phoenix-1", "fcd", None).unwrap();
+    let res = exec_purge_trash(deps.as_mut(), &env, &pie, None).unwrap();
+    assert_eq!(res.attributes[0].value, "0");
+    env.block.time = env.block.time.plus_seconds(60);
+    let res = exec_purge_trash(deps.as_mut(), &env, &pie, None).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    assert!(query_trash(deps.as_ref(), None, None).unwrap().entries.is_empty());
+    assert!(NOTE_BODIES.may_load(&deps.storage, &sha256_hex(b"v1")).unwrap().is_none());
+
+    // a purge is cut short by the entries it removes, each note's items included
+    for name in ["a-log", "b-log"] {
+        for i in 0..1000 {
+            exec_append_to_note(
+                deps.as_mut(),
+                &env,
+                &pie,
+                "station",
+                "logs",
+                name,
+                Some(NoteType::String),
+                &i.to_string(),
+                None,
+            )
+            .unwrap();
+        }
+        exec_trash_note(deps.as_mut(), &env, &pie, "station", "logs", name, None).unwrap();
+    }
+    env.block.time = env.block.time.plus_seconds(60);
+    let res = exec_purge_trash(deps.as_mut(), &env, &pie, None).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    let res = exec_purge_trash(deps.as_mut(), &env, &pie, None).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+
+    // a trashed note keeps its name until it is restored or purged
+    add(&mut deps, &env, "v3").unwrap();
+    exec_trash_note(deps.as_mut(), &env, &pie, "station", "phoenix-1", "fcd", None).unwrap();
+    let err = add(&mut deps, &env, "v4").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"Note "station"/"phoenix-1"/"fcd" is in the trash, restore or purge it first"#
+    );
+    // publishing, moving or copying onto it is refused the same way
+    let draft = NoteOptions {
+        draft: true,
+        ..NoteOptions::default()
+    };
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "phoenix-1",
+        "fcd",
+        NoteType::String,
+        "draft",
+        draft,
+    )
+    .unwrap();
+    let err = exec_publish_note(deps.as_mut(), &env, &pie, "station", "phoenix-1", "fcd", None)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::Trashed(_)));
+    exec_add_note(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "phoenix-2",
+        "fcd",
+        NoteType::String,
+        "other",
+        NoteOptions::default(),
+    )
+    .unwrap();
+    let to = NoteKey {
+        topic: "station".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: "fcd".to_string(),
+    };
+    let err = exec_move_note(deps.as_mut(), &pie, "station", "phoenix-2", "fcd", to).unwrap_err();
+    assert!(matches!(err, ContractError::Trashed(_)));
+    let err = exec_copy_sub_topic(
+        deps.as_mut(),
+        &env,
+        &pie,
+        "station",
+        "phoenix-2",
+        "phoenix-1",
+        true,
+        None,
+        None,
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Trashed(_)));
+    exec_restore_note(deps.as_mut(), &pie, "station", "phoenix-1", "fcd").unwrap();
+    let note = live_note(deps.as_ref(), &env, "station", "phoenix-1", "fcd").unwrap();
+    assert_eq!((note.note.as_str(), note.revision), ("v3", 1));
+
+    // once purged, a new note under the name starts afresh
+    exec_trash_note(deps.as_mut(), &env, &pie, "station", "phoenix-1", "fcd", None).unwrap();
+    env.block.time = env.block.time.plus_seconds(60);
+    let res = exec_purge_trash(deps.as_mut(), &env, &pie, None).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    add(&mut deps, &env, "v4").unwrap();
+    let note = live_note(deps.as_ref(), &env, "station", "phoenix-1", "fcd").unwrap();
+    assert_eq!((note.note.as_str(), note.revision), ("v4", 1));
+    assert!(query_drafts(deps.as_ref(), None, None).unwrap().entries.is_empty());
+    assert!(NOTE_BODIES.may_load(&deps.storage, &sha256_hex(b"v3")).unwrap().is_none());
+
+    exec_update_config(deps.as_mut(), None, None, Some(u64::MAX)).unwrap();
+    let err = exec_trash_note(deps.as_mut(), &env, &pie, "station", "phoenix-1", "fcd", None)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::RetentionTooLarge(u64::MAX)));
+    live_note(deps.as_ref(), &env, "station", "phoenix-1", "fcd").unwrap();
+}
+
+#[test]
+fn drafts() {
+    let mut deps = setup();
+    let env = mock_env();
+    let pie = Addr::unchecked("pie");
+    let bob = Addr::unchecked("bob");
+    exec_add_writer(deps.as_mut(), "bob", "bob").unwrap();
+    let add = |deps: &mut OwnedDeps<_, _, _>, writer: &Addr, note: &str, draft: bool| {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            writer,
+            "gov",
+            "phoenix-1",
+            "proposal",
+            NoteType::String,
+            note,
+            NoteOptions {
+                draft,
+                ..NoteOptions::default()
+            },
+        )
+    };
+
+    // a draft of a new note is only seen as a draft
+    add(&mut deps, &bob, "first draft", true).unwrap();
+    live_note(deps.as_ref(), &env, "gov", "phoenix-1", "proposal").unwrap_err();
+    assert!(query_topics(deps.as_ref(), None, None).unwrap().entries.is_empty());
+    let drafts = query_drafts(deps.as_ref(), None, None).unwrap().entries;
+    assert_eq!((drafts[0].note.as_str(), drafts[0].revision), ("first draft", 1));
+    exec_publish_note(deps.as_mut(), &env, &pie, "gov", "phoenix-1", "proposal", Some(0)).unwrap();
+    let note = live_note(deps.as_ref(), &env, "gov", "phoenix-1", "proposal").unwrap();
+    assert_eq!((note.note.as_str(), note.revision, note.writer), ("first draft", 1, bob.clone()));
+    assert!(query_drafts(deps.as_ref(), None, None).unwrap().entries.is_empty());
+
+    // the published note is served until the next draft is published
+    add(&mut deps, &bob, "second draft", true).unwrap();
+    add(&mut deps, &bob, "third draft", true).unwrap();
+    assert!(NOTE_BODIES.may_load(&deps.storage, &sha256_hex(b"second draft")).unwrap().is_none());
+    let note = live_note(deps.as_ref(), &env, "gov", "phoenix-1", "proposal").unwrap();
+    assert_eq!(note.note, "first draft");
+    add(&mut deps, &pie, "hotfix", false).unwrap();
+    let err = exec_publish_note(deps.as_mut(), &env, &pie, "gov", "phoenix-1", "proposal", Some(1))
+        .unwrap_err();
+    assert!(matches!(err, ContractError::RevisionMismatch { .. }));
+    exec_publish_note(deps.as_mut(), &env, &pie, "gov", "phoenix-1", "proposal", None).unwrap();
+    let note = live_note(deps.as_ref(), &env, "gov", "phoenix-1", "proposal").unwrap();
+    assert_eq!((note.note.as_str(), note.revision), ("third draft", 3));
+    let history = query_note_history(deps.as_ref(), "gov", "phoenix-1", "proposal", None, None)
+        .unwrap()
+        .entries;
+    assert_eq!(
+        history.iter().map(|e| e.note.as_str()).collect::<Vec<_>>(),
+        vec!["first draft", "hotfix"]
+    );
+
+    add(&mut deps, &bob, "rejected", true).unwrap();
+    exec_discard_draft(deps.as_mut(), &pie, "gov", "phoenix-1", "proposal").unwrap();
+    assert!(NOTE_BODIES.may_load(&deps.storage, &sha256_hex(b"rejected")).unwrap().is_none());
+    let err = exec_publish_note(deps.as_mut(), &env, &pie, "gov", "phoenix-1", "proposal", None)
+        .unwrap_err();
+    assert_eq!(err.to_string(), r#"Note "gov"/"phoenix-1"/"proposal" has no draft"#);
+
+    // drafts go where their notes go
+    add(&mut deps, &bob, "moving draft", true).unwrap();
+    let to = NoteKey {
+        topic: "gov".to_string(),
+        sub_topic: "phoenix-1".to_string(),
+        name: "prop-1".to_string(),
+    };
+    exec_move_note(deps.as_mut(), &pie, "gov", "phoenix-1", "proposal", to).unwrap();
+    let drafts = query_drafts(deps.as_ref(), None, None).unwrap().entries;
+    assert_eq!((drafts[0].name.as_str(), drafts[0].note.as_str()), ("prop-1", "moving draft"));
+    // and are removed with them, which is reported
+    let res = exec_rm_note(deps.as_mut(), &pie, "gov", "phoenix-1", "prop-1", None).unwrap();
+    assert_eq!(res.attributes[0].value, "1");
+    assert!(query_drafts(deps.as_ref(), None, None).unwrap().entries.is_empty());
+    assert!(NOTE_BODIES.may_load(&deps.storage, &sha256_hex(b"moving draft")).unwrap().is_none());
+    for (note, draft) in [("expiring", false), ("expiring draft", true)] {
+        exec_add_note(
+            deps.as_mut(),
+            &env,
+            &pie,
+            "gov",
+            "columbus-5",
+            "expiring",
+            NoteType::String,
+            note,
+            NoteOptions {
+                ttl_seconds: Some(60),
+                draft,
+                ..NoteOptions::default()
+            },
+        )
+        .unwrap();
+    }
+    let mut later = mock_env();
+    later.block.time = later.block.time.plus_seconds(60);
+    let res = exec_prune_expired(deps.as_mut(), &later, &pie, None).unwrap();
+    assert_eq!((res.attributes[0].value.as_str(), res.attributes[1].value.as_str()), ("1", "1"));
+    assert!(query_drafts(deps.as_ref(), None, None).unwrap().entries.is_empty());
+
+    // a draft of a new note holds up its subtopic's removal until it is gone
+    add(&mut deps, &bob, "orphan", true).unwrap();
+    let status = query_removal_status(deps.as_ref(), "gov", Some("phoenix-1".into())).unwrap();
+    assert_eq!((status.notes, status.drafts), (0, 1));
+    let res = exec_rm_sub_topic(deps.as_mut(), &pie, "gov", "phoenix-1", None).unwrap();
+    assert_eq!(res.attributes[1].value, "true");
+    assert_eq!(res.attributes[4].value, "1");
+    assert!(query_drafts(deps.as_ref(), None, None).unwrap().entries.is_empty());
+    assert!(NOTE_BODIES.may_load(&deps.storage, &sha256_hex(b"orphan")).unwrap().is_none());
+}
@@ -0,0 +1,268 @@
+use cosmwasm_std::Binary;
+use pfc_notes::{codec, Compression, NoteKey, NoteType, TopicInfo};
+
+use crate::{error::ContractError, json::Json};
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LEN: usize = 32;
+const MAX_LINKS: usize = 20;
+const MAX_FIELD_LEN: usize = 128;
+const MAX_DESCRIPTION_LEN: usize = 512;
+const MAX_OWNER_CONTACT_LEN: usize = 128;
+/// how many times larger than the stored payload a decompressed note may be
+const MAX_COMPRESSION_RATIO: usize = 16;
+
+/// checks the note body is what its `note_type` says it is
+pub fn validate_note(note_type: &NoteType, note: &str) -> Result<(), ContractError> {
+    match note_type {
+        NoteType::Json => Json::parse(note).map(|_| ()).map_err(ContractError::InvalidJson),
+        NoteType::Base64 => Binary::from_base64(note)
+            .map(|_| ())
+            .map_err(|e| ContractError::InvalidBase64(e.to_string())),
+        NoteType::Ipfs => validate_ipfs(note),
+        NoteType::String => Ok(()),
+        NoteType::Markdown => validate_markdown(note),
+        NoteType::Url => validate_url(note),
+        NoteType::Arweave => validate_arweave(note),
+        NoteType::Binary {
+            mime_type,
+        } => {
+            validate_mime_type(mime_type)?;
+            validate_note(&NoteType::Base64, note)
+        },
+    }
+}
+
+/// markdown is free text, but not binary
+fn validate_markdown(note: &str) -> Result<(), ContractError> {
+    match note.chars().find(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        Some(c) => Err(ContractError::InvalidMarkdown(format!("control character {:?}", c))),
+        None => Ok(()),
+    }
+}
+
+/// `scheme://authority[/path][?query][#fragment]`, without whitespace
+fn validate_url(note: &str) -> Result<(), ContractError> {
+    let valid = note.split_once("://").map_or(false, |(scheme, rest)| {
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            && !authority.is_empty()
+            && !note.chars().any(|c| c.is_whitespace() || c.is_control())
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(ContractError::InvalidUrl(note.to_string()))
+    }
+}
+
+/// 32 bytes as unpadded base64url, optionally as `ar://<id>`
+fn validate_arweave(note: &str) -> Result<(), ContractError> {
+    let id = note.strip_prefix("ar://").unwrap_or(note);
+    if id.len() == 43 && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')) {
+        Ok(())
+    } else {
+        Err(ContractError::InvalidArweaveId(note.to_string()))
+    }
+}
+
+/// `type/subtype` made of RFC 6838 restricted names, parameters are not allowed
+fn validate_mime_type(mime_type: &str) -> Result<(), ContractError> {
+    let is_name = |name: &str| {
+        name.len() <= 127
+            && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+    match mime_type.split_once('/') {
+        Some((kind, subtype)) if is_name(kind) && is_name(subtype) => Ok(()),
+        _ => Err(ContractError::InvalidMimeType(mime_type.to_string())),
+    }
+}
+
+/// checks the body of a possibly compressed note. `payload_limit` is the largest payload allowed
+pub fn validate_payload(
+    note_type: &NoteType,
+    compression: &Compression,
+    note: &str,
+    payload_limit: u32,
+) -> Result<(), ContractError> {
+    if *compression == Compression::None {
+        return validate_note(note_type, note);
+    }
+    let limit = (payload_limit as usize).saturating_mul(MAX_COMPRESSION_RATIO);
+    let body = codec::decode_limited(compression, note, limit)
+        .map_err(|err| ContractError::InvalidCompression(err.to_string()))?;
+    validate_note(note_type, &body)
+}
+
+/// returns the tags sorted and without duplicates
+pub fn validate_tags(mut tags: Vec<String>) -> Result<Vec<String>, ContractError> {
+    tags.sort();
+    tags.dedup();
+    if tags.len() > MAX_TAGS {
+        return Err(ContractError::TooManyTags(tags.len(), MAX_TAGS));
+    }
+    match tags.iter().find(|tag| {
+        tag.is_empty()
+            || tag.len() > MAX_TAG_LEN
+            || tag.chars().any(|c| c.is_whitespace() || c.is_control())
+    }) {
+        Some(tag) => Err(ContractError::InvalidTag(tag.clone())),
+        None => Ok(tags),
+    }
+}
+
+pub fn validate_field_name(field: &str) -> Result<(), ContractError> {
+    if field.is_empty() || field.len() > MAX_FIELD_LEN || field.chars().any(|c| c.is_control()) {
+        return Err(ContractError::InvalidFieldName(field.to_string()));
+    }
+    Ok(())
+}
+
+/// returns the links sorted and without duplicates
+pub fn validate_links(mut links: Vec<NoteKey>) -> Result<Vec<NoteKey>, ContractError> {
+    links.sort_by(|a, b| (&a.topic, &a.sub_topic, &a.name).cmp(&(&b.topic, &b.sub_topic, &b.name)));
+    links.dedup();
+    if links.len() > MAX_LINKS {
+        return Err(ContractError::TooManyLinks(links.len(), MAX_LINKS));
+    }
+    Ok(links)
+}
+
+pub fn validate_topic_info(info: &TopicInfo) -> Result<(), ContractError> {
+    if info.description.as_ref().map(|d| d.len() > MAX_DESCRIPTION_LEN).unwrap_or(false) {
+        return Err(ContractError::InvalidTopicInfo(format!(
+            "description is longer than {} bytes",
+            MAX_DESCRIPTION_LEN
+        )));
+    }
+    if let Some(owner_contact) = &info.owner_contact {
+        if owner_contact.len() > MAX_OWNER_CONTACT_LEN {
+            return Err(ContractError::InvalidTopicInfo(format!(
+                "owner contact is longer than {} bytes",
+                MAX_OWNER_CONTACT_LEN
+            )));
+        }
+        if owner_contact.chars().any(|c| c.is_control()) {
+            return Err(ContractError::InvalidTopicInfo(
+                "owner contact has control characters".to_string(),
+            ));
+        }
+    }
+    if let Some(NoteType::Binary {
+        mime_type,
+    }) = &info.default_note_type
+    {
+        validate_mime_type(mime_type)?;
+    }
+    Ok(())
+}
+
+/// accepts a bare CID, `ipfs://<cid>` or `/ipfs/<cid>`, optionally followed by a path
+fn validate_ipfs(note: &str) -> Result<(), ContractError> {
+    let path = note.strip_prefix("ipfs://").or_else(|| note.strip_prefix("/ipfs/")).unwrap_or(note);
+    let cid = path.split(['/', '?', '#']).next().unwrap_or_default();
+    if is_cid(cid) {
+        Ok(())
+    } else {
+        Err(ContractError::InvalidIpfsCid(cid.to_string()))
+    }
+}
+
+fn is_cid(cid: &str) -> bool {
+    // CIDv0 is a bare base58btc sha2-256 multihash
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        return base58_decode(cid)
+            .map(|b| b.len() == 34 && b[0] == 0x12 && b[1] == 0x20)
+            .unwrap_or(false);
+    }
+    // CIDv1 is multibase encoded
+    let Some(base) = cid.chars().next() else {
+        return false;
+    };
+    let encoded = &cid[base.len_utf8()..];
+    let bytes = match base {
+        'b' => base32_decode(encoded),
+        'B' if encoded.chars().all(|c| !c.is_ascii_lowercase()) => {
+            base32_decode(&encoded.to_ascii_lowercase())
+        },
+        'z' => base58_decode(encoded),
+        'f' if encoded.chars().all(|c| !c.is_ascii_uppercase()) => hex_decode(encoded),
+        'F' if encoded.chars().all(|c| !c.is_ascii_lowercase()) => hex_decode(encoded),
+        _ => None,
+    };
+    bytes.map(|b| is_cid_v1(&b)).unwrap_or(false)
+}
+
+/// `<version><codec><multihash>`, where the multihash is `<hash code><digest length><digest>`
+fn is_cid_v1(bytes: &[u8]) -> bool {
+    let parsed = read_varint(bytes)
+        .filter(|(version, _)| *version == 1)
+        .and_then(|(_, rest)| read_varint(rest))
+        .and_then(|(_codec, rest)| read_varint(rest))
+        .and_then(|(_hash, rest)| read_varint(rest));
+    match parsed {
+        Some((digest_len, digest)) => digest_len > 0 && digest.len() as u64 == digest_len,
+        None => false,
+    }
+}
+
+/// unsigned LEB128, as used by multiformats
+fn read_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    // little endian big number
+    let mut bytes: Vec<u8> = vec![];
+    for c in s.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let leading_zeros = s.bytes().take_while(|c| *c == b'1').count();
+    bytes.extend(std::iter::repeat(0).take(leading_zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
+/// RFC 4648 lower case, without padding
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        buffer = (buffer << 5) | BASE32_ALPHABET.iter().position(|a| *a == c)? as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
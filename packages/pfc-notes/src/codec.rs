@@ -0,0 +1,98 @@
+use std::io::Read;
+
+use cosmwasm_std::{Binary, StdError, StdResult};
+use flate2::read::GzDecoder;
+use ruzstd::StreamingDecoder;
+
+use crate::Compression;
+
+/// compresses `body`, returning the payload to store as the note. the payload of a compressed
+/// note is base64 encoded. zstd goes through the C library, so this is for clients, the
+/// contract only decodes
+#[cfg(feature = "encode")]
+pub fn encode(compression: &Compression, body: &str) -> StdResult<String> {
+    use std::io::Write;
+
+    let bytes = match compression {
+        Compression::None => return Ok(body.to_string()),
+        Compression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(body.as_bytes()).map_err(codec_err)?;
+            encoder.finish().map_err(codec_err)?
+        },
+        Compression::Zstd => zstd::encode_all(body.as_bytes(), 19).map_err(codec_err)?,
+    };
+    Ok(Binary::from(bytes).to_base64())
+}
+
+/// the note body of an `encode`d payload
+pub fn decode(compression: &Compression, payload: &str) -> StdResult<String> {
+    decode_limited(compression, payload, usize::MAX)
+}
+
+/// as `decode`, failing once the body grows past `limit` bytes
+pub fn decode_limited(compression: &Compression, payload: &str, limit: usize) -> StdResult<String> {
+    let bytes = match compression {
+        Compression::None => payload.as_bytes().to_vec(),
+        Compression::Gzip => {
+            let compressed = Binary::from_base64(payload)?;
+            read_limited(GzDecoder::new(compressed.as_slice()), limit)?
+        },
+        Compression::Zstd => {
+            let compressed = Binary::from_base64(payload)?;
+            let decoder = StreamingDecoder::new(compressed.as_slice()).map_err(codec_err)?;
+            read_limited(decoder, limit)?
+        },
+    };
+    if bytes.len() > limit {
+        return Err(StdError::generic_err(format!("decoded note is larger than {} bytes", limit)));
+    }
+    String::from_utf8(bytes).map_err(codec_err)
+}
+
+/// reads at most one byte past `limit`, enough to tell the body is too large
+fn read_limited<R: Read>(reader: R, limit: usize) -> StdResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(limit.saturating_add(1) as u64).read_to_end(&mut bytes).map_err(codec_err)?;
+    Ok(bytes)
+}
+
+fn codec_err(err: impl ToString) -> StdError {
+    StdError::generic_err(err.to_string())
+}
+
+#[cfg(all(test, feature = "encode"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let body =
+            format!("[{}]", vec![r#"{"validator":"pfc","commission":"0.05"}"#; 50].join(","));
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let payload = encode(&compression, &body).unwrap();
+            assert_eq!(decode(&compression, &payload).unwrap(), body);
+            if compression != Compression::None {
+                assert!(payload.len() < body.len() / 4);
+            }
+        }
+        assert_eq!(decode(&Compression::None, "é").unwrap(), "é");
+        assert!(decode(&Compression::Gzip, "not base64!").is_err());
+        assert!(decode(&Compression::Zstd, &Binary::from(b"not zstd").to_base64()).is_err());
+    }
+
+    #[test]
+    fn bombs_stop_at_the_limit() {
+        let body = "a".repeat(1024 * 1024);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let payload = encode(&compression, &body).unwrap();
+            assert!(payload.len() < 8 * 1024);
+            assert_eq!(decode_limited(&compression, &payload, body.len()).unwrap(), body);
+            let err = decode_limited(&compression, &payload, 1000).unwrap_err();
+            assert_eq!(err.to_string(), "Generic error: decoded note is larger than 1000 bytes");
+        }
+        let err = decode_limited(&Compression::None, "abcd", 3).unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: decoded note is larger than 3 bytes");
+    }
+}
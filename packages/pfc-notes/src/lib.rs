@@ -1,36 +1,76 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, BlockInfo, Timestamp};
 use cw_ownable::{cw_ownable_execute, cw_ownable_query};
 
+#[cfg(feature = "codec")]
+pub mod codec;
+
 #[cw_serde]
 pub enum NoteType {
-    Json = 1,
-    String = 2,
-    Ipfs = 3,
-    Base64 = 4,
+    Json,
+    String,
+    Ipfs,
+    Base64,
+    Markdown,
+    Url,
+    /// an Arweave transaction id
+    Arweave,
+    /// base64 encoded bytes of the given MIME type
+    Binary {
+        mime_type: String,
+    },
 }
 
-impl ToString for NoteType {
-    fn to_string(&self) -> String {
+impl fmt::Display for NoteType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self {
-            NoteType::Ipfs => String::from("IPFS"),
-            NoteType::String => String::from("String"),
-            NoteType::Json => String::from("Json"),
-            NoteType::Base64 => String::from("Base64"),
+            NoteType::Ipfs => write!(f, "IPFS"),
+            NoteType::String => write!(f, "String"),
+            NoteType::Json => write!(f, "Json"),
+            NoteType::Base64 => write!(f, "Base64"),
+            NoteType::Markdown => write!(f, "Markdown"),
+            NoteType::Url => write!(f, "Url"),
+            NoteType::Arweave => write!(f, "Arweave"),
+            NoteType::Binary {
+                mime_type,
+            } => write!(f, "Binary:{}", mime_type),
         }
     }
 }
 
+/// how a note's payload is compressed. see `codec` for encoding and decoding payloads
+#[cw_serde]
+#[derive(Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// parses what `Display` writes. names are not case sensitive, so "JSON" is also accepted
 impl FromStr for NoteType {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "IPFS" => Ok(NoteType::Ipfs),
-            "JSON" => Ok(NoteType::Json),
-            "String" => Ok(NoteType::String),
-            "Base64" => Ok(NoteType::Base64),
+        if let Some((kind, mime_type)) = s.split_once(':') {
+            return if kind.eq_ignore_ascii_case("binary") {
+                Ok(NoteType::Binary {
+                    mime_type: mime_type.to_string(),
+                })
+            } else {
+                Err(())
+            };
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "ipfs" => Ok(NoteType::Ipfs),
+            "json" => Ok(NoteType::Json),
+            "string" => Ok(NoteType::String),
+            "base64" => Ok(NoteType::Base64),
+            "markdown" => Ok(NoteType::Markdown),
+            "url" => Ok(NoteType::Url),
+            "arweave" => Ok(NoteType::Arweave),
             _ => Err(()),
         }
     }
@@ -42,11 +82,113 @@ pub struct NoteKey {
     pub sub_topic: String,
     pub name: String,
 }
-impl ToString for NoteKey {
-    fn to_string(&self) -> String {
-        format!("{}_{}:{}", self.topic, self.sub_topic, self.name)
+/// each part is quoted, so keys whose parts contain separators can not be confused
+impl fmt::Display for NoteKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}/{:?}/{:?}", self.topic, self.sub_topic, self.name)
+    }
+}
+
+impl From<(&str, &str, &str)> for NoteKey {
+    fn from((topic, sub_topic, name): (&str, &str, &str)) -> Self {
+        NoteKey {
+            topic: topic.to_string(),
+            sub_topic: sub_topic.to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+/// block height and time a note was written at
+#[cw_serde]
+pub struct NoteStamp {
+    pub height: u64,
+    pub time: Timestamp,
+}
+impl From<&BlockInfo> for NoteStamp {
+    fn from(block: &BlockInfo) -> Self {
+        NoteStamp {
+            height: block.height,
+            time: block.time,
+        }
     }
 }
+
+/// how a note's content is held and updated
+#[cw_serde]
+#[derive(Default)]
+pub enum NoteKind {
+    /// the note body, written by `AddNote`
+    #[default]
+    Plain,
+    /// items appended by `AppendToNote` and read with `ListItems`. the note body is empty
+    List {
+        /// the oldest items are dropped past this many
+        capacity: Option<u32>,
+    },
+    /// a number changed by `IncrementNote` and `DecrementNote`. the note body is empty
+    Counter {
+        value: u64,
+    },
+    /// fields written by `SetNoteField` and read with `NoteFields`. the note body is empty
+    Map {
+        /// the number of fields held
+        fields: u32,
+        /// bytes held in the field names and values
+        #[serde(default)]
+        size: u64,
+    },
+}
+
+impl fmt::Display for NoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteKind::Plain => write!(f, "plain"),
+            NoteKind::List {
+                ..
+            } => write!(f, "list"),
+            NoteKind::Counter {
+                ..
+            } => write!(f, "counter"),
+            NoteKind::Map {
+                ..
+            } => write!(f, "map"),
+        }
+    }
+}
+
+#[cw_serde]
+pub struct ListItem {
+    pub index: u64,
+    pub item: String,
+    pub writer: Addr,
+    pub appended_at: NoteStamp,
+}
+
+/// another name for a note. `Note` queries on the alias return its target
+#[cw_serde]
+pub struct NoteAlias {
+    pub target: NoteKey,
+    pub writer: Addr,
+    pub updated_at: NoteStamp,
+}
+
+#[cw_serde]
+pub struct NoteField {
+    pub field: String,
+    pub value: String,
+    pub writer: Addr,
+    pub updated_at: NoteStamp,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub enum ListOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
 #[cw_serde]
 pub struct NoteEntry {
     pub writer: Addr,
@@ -55,6 +197,44 @@ pub struct NoteEntry {
     pub name: String,
     pub note_type: NoteType,
     pub note: String,
+    /// when the note was first written. kept when the note is overwritten
+    pub created_at: NoteStamp,
+    /// when the note was last written
+    pub updated_at: NoteStamp,
+    /// number of times the note has been written, starting at 1
+    pub revision: u64,
+    /// the note is no longer served after this time
+    pub expires_at: Option<Timestamp>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// the note holds the base64 encoded, compressed body
+    #[serde(default)]
+    pub compression: Compression,
+    /// hex encoded sha256 of `note`, as stored
+    pub hash: String,
+    /// notes this note refers to. they need not exist
+    #[serde(default)]
+    pub links: Vec<NoteKey>,
+    #[serde(default)]
+    pub kind: NoteKind,
+}
+
+#[cfg(feature = "codec")]
+impl NoteEntry {
+    /// the note body, decompressed
+    pub fn body(&self) -> cosmwasm_std::StdResult<String> {
+        codec::decode(&self.compression, &self.note)
+    }
+}
+
+/// the answer to `QueryMsg::Note`. an expired note is kept until it is pruned, but not served
+#[cw_serde]
+pub enum NoteStatus {
+    Live(Box<NoteEntry>),
+    Expired {
+        key: NoteKey,
+        expired_at: Timestamp,
+    },
 }
 
 #[cw_serde]
@@ -62,9 +242,9 @@ pub struct SubTopicKey {
     pub topic: String,
     pub sub_topic: String,
 }
-impl ToString for SubTopicKey {
-    fn to_string(&self) -> String {
-        format!("{}_{}", self.topic, self.sub_topic)
+impl fmt::Display for SubTopicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.topic, self.sub_topic)
     }
 }
 
@@ -74,10 +254,47 @@ pub struct NoteWriter {
     pub name: String,
 }
 
+/// a change to a `NoteType::Json` note
+#[cw_serde]
+pub enum NotePatch {
+    /// an RFC 7396 merge patch document
+    Merge(String),
+    /// an RFC 6902 patch, a JSON array of operations
+    Json(String),
+}
+
+/// what a topic or subtopic is for
+#[cw_serde]
+#[derive(Default)]
+pub struct TopicInfo {
+    pub description: Option<String>,
+    /// who to ask about the topic's notes
+    pub owner_contact: Option<String>,
+    /// used by `AddNote` when it does not give a note type
+    pub default_note_type: Option<NoteType>,
+}
+
+#[cw_serde]
+pub struct Topic {
+    pub topic: String,
+    pub info: TopicInfo,
+}
+
 #[cw_serde]
 pub struct Subtopic {
     pub topic: String,
     pub sub_topic: String,
+    pub info: TopicInfo,
+}
+
+#[cw_serde]
+pub struct Config {
+    /// how many previous revisions of each note are kept
+    pub history_depth: u32,
+    /// largest note body accepted, in bytes, unless the topic has its own limit
+    pub max_note_size: u32,
+    /// seconds a removed note stays in the trash before it can be purged
+    pub trash_retention: u64,
 }
 
 #[cw_serde]
@@ -85,6 +302,12 @@ pub struct InstantiateMsg {
     /// The account to be appointed the contract owner
     pub owner: String,
     pub writers: Vec<NoteWriter>,
+    /// how many previous revisions of each note are kept. defaults to 5
+    pub history_depth: Option<u32>,
+    /// largest note body accepted, in bytes, above zero. defaults to 32KiB
+    pub max_note_size: Option<u32>,
+    /// seconds a removed note stays in the trash before it can be purged. defaults to 30 days
+    pub trash_retention: Option<u64>,
 }
 
 #[cw_ownable_execute]
@@ -101,20 +324,260 @@ pub enum ExecuteMsg {
         topic: String,
         sub_topic: String,
         name: String,
-        note_type: NoteType,
+        /// defaults to the subtopic's default note type, then the topic's
+        note_type: Option<NoteType>,
         note: String,
+        /// stop serving the note after this time
+        expires_at: Option<Timestamp>,
+        /// stop serving the note this many seconds after it is written. exclusive with `expires_at`
+        ttl_seconds: Option<u64>,
+        /// fail unless the stored note is at this revision. 0 means the note must not exist yet
+        expected_revision: Option<u64>,
+        /// replaces the note's tags. existing tags are kept when not set
+        tags: Option<Vec<String>>,
+        /// `note` is compressed, see `codec::encode`
+        compression: Option<Compression>,
+        /// replaces the notes this note refers to. existing links are kept when not set
+        links: Option<Vec<NoteKey>>,
+        /// save the note as the note's draft, leaving the published note served until
+        /// `PublishNote`
+        draft: Option<bool>,
+    },
+    /// makes the note's draft its latest revision
+    PublishNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        /// fail unless the published note is at this revision
+        expected_revision: Option<u64>,
+    },
+    DiscardDraft {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// appends an item to a list note, creating the list if needed. `capacity` replaces the
+    /// list's capacity when set. a list holds at most 1MiB across its items. a capped list drops
+    /// its oldest items to stay within that, appending to any other fails
+    AppendToNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        /// the type of every item in a new list. defaults as for `AddNote`
+        note_type: Option<NoteType>,
+        item: String,
+        capacity: Option<u32>,
+    },
+    /// sets one field of a map note, creating the map if needed. a map holds at most 1MiB across
+    /// its field names and values
+    SetNoteField {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        /// the type of every value in a new map. defaults as for `AddNote`
+        note_type: Option<NoteType>,
+        field: String,
+        value: String,
+    },
+    RemoveNoteField {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        field: String,
+    },
+    /// points a name at another note, which may itself be an alias
+    /// the chain through it, counting aliases already pointing at the name, stays loop free and
+    /// within 8 aliases of a note
+    SetAlias {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        target: NoteKey,
+    },
+    RemoveAlias {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// adds to a counter note, creating it at 0 if needed
+    IncrementNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        /// defaults to 1
+        by: Option<u64>,
     },
+    /// takes from a counter note. counters do not go below 0
+    DecrementNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        /// defaults to 1
+        by: Option<u64>,
+    },
+    /// rewrites a Json note by applying the patch to its body. the result is stored uncompressed,
+    /// written out again with object keys sorted and no whitespace, so the bytes can change even
+    /// when the document does not. numbers keep the digits they were written with. the note's
+    /// expiry is kept, even one that has already passed
+    PatchNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        patch: NotePatch,
+        /// fail unless the stored note is at this revision
+        expected_revision: Option<u64>,
+    },
+    /// removes a note with its history and items. unless it goes to the trash, its draft goes too,
+    /// and the `removed_drafts` attribute counts it
     RemoveNote {
         topic: String,
         sub_topic: String,
         name: String,
+        /// fail unless the stored note is at this revision
+        expected_revision: Option<u64>,
+        /// move the note to the trash, where it can be restored until it is purged. it keeps
+        /// its name till then, no note can be added, published, moved or copied onto it
+        soft: Option<bool>,
     },
+    /// brings a note back from the trash
+    RestoreNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// removes up to `limit` notes that have been in the trash longer than `trash_retention`. a
+    /// batch ends early once it has removed 1000 entries
+    PurgeTrash {
+        limit: Option<u32>,
+    },
+    /// gives a note a new key, keeping its writer, stamps, revisions, items and draft. aliases
+    /// and links that point at the note follow it. fails while an upload to the note is in
+    /// progress, finish or abort it first
+    MoveNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        to: NoteKey,
+    },
+    /// moves up to `limit` notes, aliases, trashed notes and drafts to the new subtopic, and the
+    /// subtopic itself once it is empty. a batch ends early once it has rewritten 1000 entries,
+    /// counting history, items, fields and the aliases and links that follow each note. repeat
+    /// until the response reports `done`. fails while an upload to the subtopic is in progress
+    RenameSubTopic {
+        topic: String,
+        sub_topic: String,
+        new_sub_topic: String,
+        limit: Option<u32>,
+    },
+    /// moves up to `limit` notes, aliases, trashed notes, drafts and subtopics to the new topic,
+    /// and the topic itself once it is empty. batches end early as for `RenameSubTopic`. repeat
+    /// until the response reports `done`. fails while an upload to the topic is in progress
+    RenameTopic {
+        topic: String,
+        new_topic: String,
+        limit: Option<u32>,
+    },
+    /// copies up to `limit` notes, in name order after `start_after`, into another subtopic of
+    /// the topic. the copies start at revision 1 with the sender as writer, and drafts are not
+    /// copied. with `overwrite` an existing note is removed first, losing its history, items and
+    /// draft, counted in the `removed_drafts` attribute. otherwise it is left alone. a batch ends
+    /// early as for `RenameSubTopic`. repeat from the `last` attribute until the response reports
+    /// `done`
+    CopySubTopic {
+        topic: String,
+        from: String,
+        to: String,
+        overwrite: bool,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// removes up to `limit` notes, aliases, trashed notes, drafts and uploads from the subtopic,
+    /// and the subtopic itself once it is empty. a batch ends early once it has removed 1000 entries,
+    /// counting history, items and fields. `removed_drafts` counts the drafts of removed notes
+    /// too. repeat until the response reports `done`
     RemoveSubTopic {
         topic: String,
         sub_topic: String,
+        limit: Option<u32>,
     },
+    /// removes up to `limit` notes, aliases, trashed notes, drafts, uploads and subtopics from the
+    /// topic, and the topic itself once it is empty. a batch ends early as for `RemoveSubTopic`. repeat
+    /// until the response reports `done`
     RemoveTopic {
         topic: String,
+        limit: Option<u32>,
+    },
+    /// remove up to `limit` notes that have expired, with their drafts. the `removed_drafts`
+    /// attribute counts those. a batch ends early once it has removed 1000 entries
+    PruneExpired {
+        limit: Option<u32>,
+    },
+    /// write a previous revision of a note back as its latest revision
+    RevertNote {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        revision: u64,
+    },
+    /// start assembling a note from `total_chunks` chunks. replaces any upload in progress.
+    /// `total_size` is the length of the assembled note, which is held to the topic's
+    /// `max_note_size` like any other note
+    BeginUpload {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        /// defaults to the subtopic's, then the topic's, default note type
+        note_type: Option<NoteType>,
+        total_chunks: u32,
+        total_size: u32,
+        /// hex encoded sha256 of the assembled note
+        sha256: String,
+    },
+    UploadChunk {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        index: u32,
+        data: String,
+    },
+    /// drops the upload in progress for the note, with the chunks received so far
+    AbortUpload {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// joins the chunks in order and writes them as the note once the hash matches
+    FinalizeUpload {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// replaces the topic's info
+    SetTopicInfo {
+        topic: String,
+        info: TopicInfo,
+    },
+    /// replaces the subtopic's info
+    SetSubTopicInfo {
+        topic: String,
+        sub_topic: String,
+        info: TopicInfo,
+    },
+    /// moves up to `limit` more legacy notes and subtopics to the current storage layout, after a
+    /// migration that did not finish. repeat until the response reports `done`
+    MigrateStorage {
+        limit: Option<u32>,
+    },
+    UpdateConfig {
+        history_depth: Option<u32>,
+        max_note_size: Option<u32>,
+        trash_retention: Option<u64>,
+    },
+    /// overrides the contract's `max_note_size` for a topic, until the topic is removed. `None`
+    /// removes the override. a limit of zero is refused
+    SetTopicNoteSize {
+        topic: String,
+        max_note_size: Option<u32>,
     },
 }
 
@@ -123,6 +586,15 @@ pub struct NoteResponse<T> {
     pub entries: Vec<T>,
 }
 
+/// a page of live notes. expired notes are skipped but count towards the 100 notes a page looks
+/// at, so a page can come back short, or empty, with more to follow
+#[cw_serde]
+pub struct NotePage<K> {
+    pub entries: Vec<NoteEntry>,
+    /// pass as `start_after` for the next page. `None` once there is nothing left
+    pub start_after: Option<K>,
+}
+
 #[cw_ownable_query]
 #[cw_serde]
 #[derive(QueryResponses)]
@@ -132,7 +604,7 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
-    #[returns(NoteResponse < String >)]
+    #[returns(NoteResponse < Topic >)]
     Topics {
         start_after: Option<String>,
         limit: Option<u32>,
@@ -143,20 +615,199 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
-    #[returns(NoteResponse < NoteKey >)]
+    #[returns(NotePage < String >)]
     Entries {
         topic: String,
         sub_topic: String,
         start_after: Option<String>,
         limit: Option<u32>,
     },
-    #[returns(NoteResponse < NoteEntry >)]
+    /// aliases are followed to the note they name
+    #[returns(NoteStatus)]
     Note {
         topic: String,
         sub_topic: String,
         name: String,
     },
+    /// drafts waiting to be published, ordered by key
+    #[returns(NoteResponse<NoteEntry>)]
+    Drafts {
+        start_after: Option<NoteKey>,
+        limit: Option<u32>,
+    },
+    /// notes in the trash, ordered by key
+    #[returns(NoteResponse<TrashedNote>)]
+    Trash {
+        start_after: Option<NoteKey>,
+        limit: Option<u32>,
+    },
+    /// the alias itself, without following it
+    #[returns(NoteAlias)]
+    Alias {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// previous revisions of a note, oldest first. the current revision is not included
+    #[returns(NoteResponse < NoteEntry >)]
+    NoteHistory {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(NoteEntry)]
+    NoteAtRevision {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        revision: u64,
+    },
+    #[returns(Config)]
+    Config {},
+    #[returns(RemovalStatus)]
+    RemovalStatus {
+        topic: String,
+        sub_topic: Option<String>,
+    },
+    #[returns(UploadStatus)]
+    Upload {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// a piece of a note's body. pieces are `chunk_size` bytes, adjusted to character boundaries
+    #[returns(NoteChunk)]
+    NoteChunk {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        index: u32,
+        chunk_size: Option<u32>,
+    },
+    /// hex encoded sha256 of the note as stored, for checking a copy is current
+    #[returns(String)]
+    NoteHash {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    /// every note stored with the given hash
+    #[returns(NotePage<NoteKey>)]
+    NotesByHash {
+        hash: String,
+        start_after: Option<NoteKey>,
+        limit: Option<u32>,
+    },
+    /// every note linking to the given one
+    #[returns(Backlinks)]
+    Backlinks {
+        topic: String,
+        sub_topic: String,
+        name: String,
+        start_after: Option<NoteKey>,
+        limit: Option<u32>,
+    },
+    /// the links of a note that reach no note, directly or through aliases
+    #[returns(Vec<NoteKey>)]
+    DanglingLinks {
+        topic: String,
+        sub_topic: String,
+        name: String,
+    },
+    #[returns(NoteResponse<ListItem>)]
+    ListItems {
+        key: NoteKey,
+        /// an item index
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order: Option<ListOrder>,
+    },
+    #[returns(NoteResponse<NoteField>)]
+    NoteFields {
+        key: NoteKey,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// the largest note body accepted for the topic, in bytes
+    #[returns(u32)]
+    MaxNoteSize {
+        topic: String,
+    },
+    #[returns(NotePage < NoteKey >)]
+    NotesByTag {
+        tag: String,
+        start_after: Option<NoteKey>,
+        limit: Option<u32>,
+    },
+}
+
+/// what is left under a topic (or subtopic) being removed. each count stops at 100, so 100 means
+/// at least that many
+#[cw_serde]
+pub struct RemovalStatus {
+    pub sub_topics: u64,
+    pub notes: u64,
+    #[serde(default)]
+    pub aliases: u64,
+    #[serde(default)]
+    pub trashed: u64,
+    #[serde(default)]
+    pub drafts: u64,
+    #[serde(default)]
+    pub uploads: u64,
+}
+
+/// a soft deleted note
+#[cw_serde]
+pub struct TrashedNote {
+    pub note: NoteEntry,
+    pub removed_by: Addr,
+    pub removed_at: NoteStamp,
+    /// the note can be purged from this time on
+    pub purge_after: Timestamp,
 }
 
+/// a note being assembled from chunks
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct PendingUpload {
+    pub uploader: Addr,
+    pub note_type: NoteType,
+    pub total_chunks: u32,
+    pub total_size: u32,
+    /// bytes in the chunks received so far
+    pub received_size: u32,
+    /// hex encoded sha256 of the assembled note
+    pub sha256: String,
+    pub started_at: NoteStamp,
+}
+
+#[cw_serde]
+pub struct UploadStatus {
+    pub upload: PendingUpload,
+    /// indexes of the chunks received so far
+    pub received: Vec<u32>,
+}
+
+#[cw_serde]
+pub struct NoteChunk {
+    pub index: u32,
+    pub total_chunks: u32,
+    pub data: String,
+}
+
+#[cw_serde]
+pub struct Backlinks {
+    /// false when no note is under the key, directly or through aliases, so every link to it
+    /// dangles
+    pub exists: bool,
+    pub entries: Vec<NoteKey>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {
+    /// the most legacy notes and subtopics moved by the migration. the rest are moved with
+    /// `MigrateStorage`
+    pub limit: Option<u32>,
+}